@@ -0,0 +1,104 @@
+//! Extraction benchmarks: large reads in both bit orders, against the
+//! per-bit loops they replaced.
+//!
+//! Run with `cargo bench --bench extract`.
+
+use bitvec::prelude::*;
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use itty_bitty::{BitOrder, BitReader};
+use num_bigint::BigUint;
+
+/// The original MSB extraction: one bit at a time into a byte buffer.
+fn per_bit_msb(bits: &BitSlice<u8, Msb0>) -> BigUint {
+    let n = bits.len();
+    let num_bytes = n.div_ceil(8);
+    let mut bytes = vec![0u8; num_bytes];
+    let padding = num_bytes * 8 - n;
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            let abs_pos = padding + i;
+            bytes[abs_pos / 8] |= 1 << (7 - abs_pos % 8);
+        }
+    }
+    BigUint::from_bytes_be(&bytes)
+}
+
+/// The original LSB extraction: a BigUint shift-and-OR per set bit.
+fn per_bit_lsb(bits: &BitSlice<u8, Lsb0>) -> BigUint {
+    let mut result = BigUint::ZERO;
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            result |= BigUint::from(1u8) << i;
+        }
+    }
+    result
+}
+
+fn test_data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 0x9d + 0x35) as u8).collect()
+}
+
+fn wide_reads(c: &mut Criterion) {
+    let data = test_data(1 << 20);
+    let reader = BitReader::new(&data);
+    let mut group = c.benchmark_group("wide_read");
+    group.sample_size(10);
+
+    for bytes in [1 << 10, 1 << 14] {
+        // Start 3 bits in so nothing is byte-aligned
+        let range = 3..3 + bytes * 8;
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_with_input(BenchmarkId::new("msb", bytes), &range, |b, range| {
+            b.iter(|| reader.read(black_box(range.clone()), BitOrder::Msb))
+        });
+        group.bench_with_input(BenchmarkId::new("lsb", bytes), &range, |b, range| {
+            b.iter(|| reader.read(black_box(range.clone()), BitOrder::Lsb))
+        });
+        group.bench_with_input(BenchmarkId::new("msb_per_bit", bytes), &range, |b, range| {
+            let bits = BitSlice::<u8, Msb0>::from_slice(&data);
+            b.iter(|| per_bit_msb(&bits[black_box(range.clone())]))
+        });
+        // Quadratic, so only at the smaller size
+        if bytes <= 1 << 10 {
+            group.bench_with_input(BenchmarkId::new("lsb_per_bit", bytes), &range, |b, range| {
+                let bits = BitSlice::<u8, Lsb0>::from_slice(&data);
+                b.iter(|| per_bit_lsb(&bits[black_box(range.clone())]))
+            });
+        }
+    }
+
+    // Multi-megabyte reads, where the per-bit LSB loop never finishes
+    let range = 3..data.len() * 8 - 5;
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.bench_function("lsb_1MiB", |b| {
+        b.iter(|| reader.read(black_box(range.clone()), BitOrder::Lsb))
+    });
+    group.bench_function("msb_1MiB", |b| {
+        b.iter(|| reader.read(black_box(range.clone()), BitOrder::Msb))
+    });
+    group.finish();
+}
+
+fn narrow_reads(c: &mut Criterion) {
+    let data = test_data(4096);
+    let reader = BitReader::new(&data);
+    let mut group = c.benchmark_group("narrow_read");
+
+    for width in [13, 64, 128] {
+        let range = 5..5 + width;
+        group.bench_with_input(BenchmarkId::new("u128", width), &range, |b, range| {
+            b.iter(|| reader.read_u128(black_box(range.clone()), BitOrder::Lsb))
+        });
+        group.bench_with_input(BenchmarkId::new("biguint", width), &range, |b, range| {
+            b.iter(|| reader.read(black_box(range.clone()), BitOrder::Lsb))
+        });
+        group.bench_with_input(BenchmarkId::new("lsb_per_bit", width), &range, |b, range| {
+            let bits = BitSlice::<u8, Lsb0>::from_slice(&data);
+            b.iter(|| per_bit_lsb(&bits[black_box(range.clone())]))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, wide_reads, narrow_reads);
+criterion_main!(benches);
@@ -0,0 +1,137 @@
+//! Finding members of zip and tar archives, so a field can be read from a
+//! member without extracting it first.
+
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+use zip::{CompressionMethod, ZipArchive};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Tar => "tar",
+        }
+    }
+
+    /// Recognise an archive by its magic number: a zip local file header or
+    /// empty-archive record, or a POSIX/GNU tar header.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if data.get(257..262) == Some(b"ustar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Where a member's data lies within its archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Member {
+    pub name: String,
+    pub format: ArchiveFormat,
+    /// Byte offset of the member's data in the archive
+    pub data_offset: u64,
+    /// Bytes the data takes up in the archive
+    pub stored_size: u64,
+    /// Bytes of the member's contents
+    pub size: u64,
+    /// The stored data is compressed (zip only), so the contents aren't a
+    /// plain slice of the archive
+    pub compressed: bool,
+}
+
+/// Split `archive.zip::dir/member` into the file and the chain of members,
+/// one per `::` so archives can nest. A path that exists as given is never
+/// split.
+pub fn split_member_path(path: &str) -> (&str, Vec<&str>) {
+    if path == "-" || Path::new(path).exists() {
+        return (path, Vec::new());
+    }
+    let mut parts = path.split("::");
+    let file = parts.next().unwrap_or(path);
+    (file, parts.collect())
+}
+
+fn not_found(name: &str, format: ArchiveFormat) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no member '{}' in {} archive", name, format.name()),
+    )
+}
+
+/// Find member `name` in the zip or tar archive `data`.
+pub fn find_member(data: &[u8], name: &str) -> io::Result<Member> {
+    match ArchiveFormat::detect(data) {
+        Some(ArchiveFormat::Zip) => find_zip_member(data, name),
+        Some(ArchiveFormat::Tar) => find_tar_member(data, name),
+        // Self-extracting and other prefixed zips only show at the end
+        None => find_zip_member(data, name).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "not a zip or tar archive")
+        }),
+    }
+}
+
+fn find_zip_member(data: &[u8], name: &str) -> io::Result<Member> {
+    let mut zip = ZipArchive::new(Cursor::new(data))?;
+    let file = zip.by_name(name).map_err(|e| match e {
+        zip::result::ZipError::FileNotFound => not_found(name, ArchiveFormat::Zip),
+        e => e.into(),
+    })?;
+    if file.encrypted() {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("member '{}' is encrypted", name),
+        ));
+    }
+    let data_offset = file.data_start().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("can't find where member '{}' starts", name),
+        )
+    })?;
+    Ok(Member {
+        name: name.to_string(),
+        format: ArchiveFormat::Zip,
+        data_offset,
+        stored_size: file.compressed_size(),
+        size: file.size(),
+        compressed: file.compression() != CompressionMethod::Stored,
+    })
+}
+
+fn find_tar_member(data: &[u8], name: &str) -> io::Result<Member> {
+    let wanted = name.trim_start_matches("./");
+    let mut tar = tar::Archive::new(Cursor::new(data));
+    for entry in tar.entries_with_seek()? {
+        let entry = entry?;
+        let path = entry.path()?;
+        if path.to_string_lossy().trim_start_matches("./") == wanted {
+            return Ok(Member {
+                name: name.to_string(),
+                format: ArchiveFormat::Tar,
+                data_offset: entry.raw_file_position(),
+                stored_size: entry.size(),
+                size: entry.size(),
+                compressed: false,
+            });
+        }
+    }
+    Err(not_found(name, ArchiveFormat::Tar))
+}
+
+/// Decompress a member whose data is compressed within the archive.
+pub fn read_member(data: &[u8], member: &Member) -> io::Result<Vec<u8>> {
+    let mut zip = ZipArchive::new(Cursor::new(data))?;
+    let mut file = zip.by_name(&member.name)?;
+    let mut out = Vec::with_capacity(member.size as usize);
+    file.read_to_end(&mut out)?;
+    Ok(out)
+}
@@ -0,0 +1,79 @@
+//! Decompressing gzip, bzip2, xz and zstd inputs so that offsets apply to
+//! the payload rather than the container.
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use std::io::{self, Read};
+use xz2::read::XzDecoder;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Compression {
+    #[cfg_attr(feature = "cli", value(alias = "gz"))]
+    Gzip,
+    #[cfg_attr(feature = "cli", value(alias = "bz2"))]
+    Bzip2,
+    Xz,
+    #[cfg_attr(feature = "cli", value(alias = "zst"))]
+    Zstd,
+}
+
+/// `gzip` (`gz`), `bzip2` (`bz2`), `xz` or `zstd` (`zst`).
+impl std::str::FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_name(
+            s,
+            "compression",
+            &[
+                ("gzip", Compression::Gzip),
+                ("gz", Compression::Gzip),
+                ("bzip2", Compression::Bzip2),
+                ("bz2", Compression::Bzip2),
+                ("xz", Compression::Xz),
+                ("zstd", Compression::Zstd),
+                ("zst", Compression::Zstd),
+            ],
+        )
+    }
+}
+
+impl Compression {
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Recognise a compressed stream by its magic number.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if data.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decompress all of `data`, including concatenated streams (as `cat a.gz
+/// b.gz` produces), which decode to the concatenated payloads.
+pub fn decompress(data: &[u8], format: Compression) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match format {
+        Compression::Gzip => MultiGzDecoder::new(data).read_to_end(&mut out)?,
+        Compression::Bzip2 => MultiBzDecoder::new(data).read_to_end(&mut out)?,
+        Compression::Xz => XzDecoder::new_multi_decoder(data).read_to_end(&mut out)?,
+        Compression::Zstd => zstd::stream::read::Decoder::new(data)?.read_to_end(&mut out)?,
+    };
+    Ok(out)
+}
@@ -0,0 +1,117 @@
+//! Bit-level comparison of two ranges, in the same data or different data.
+//!
+//! Both ranges are realigned to bytes with
+//! [`BitReader::aligned_chunks`](crate::BitReader::aligned_chunks), so they can
+//! start at any bit and are still compared a byte at a time.
+
+use crate::{BitOrder, CHUNK_BYTES};
+use crate::reader::{BitReader, Padding, ReadError};
+use std::ops::Range;
+
+/// The runs of differing bits between `a` and `b`, as offsets from the start
+/// of each range. Only as many bits as the shorter range has are compared.
+pub fn diff_ranges(
+    a: &BitReader,
+    a_range: Range<usize>,
+    b: &BitReader,
+    b_range: Range<usize>,
+    order: BitOrder,
+) -> Result<Vec<Range<usize>>, ReadError> {
+    let len = a_range.len().min(b_range.len());
+    let a_range = a_range.start..a_range.start + len;
+    let b_range = b_range.start..b_range.start + len;
+    let a_chunks = a.aligned_chunks(a_range, order, Padding::Zero, CHUNK_BYTES)?;
+    let b_chunks = b.aligned_chunks(b_range, order, Padding::Zero, CHUNK_BYTES)?;
+
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    let mut offset = 0;
+    for (a_chunk, b_chunk) in a_chunks.zip(b_chunks) {
+        for (x, y) in a_chunk.iter().zip(&b_chunk) {
+            let changed = x ^ y;
+            if changed != 0 {
+                for bit in 0..8 {
+                    // The first bit of an aligned byte is the top one for
+                    // MSB, the bottom one for LSB
+                    let set = match order {
+                        BitOrder::Msb => changed & (0x80 >> bit),
+                        BitOrder::Lsb => changed & (1 << bit),
+                    };
+                    if set == 0 {
+                        continue;
+                    }
+                    let at = offset + bit;
+                    match runs.last_mut() {
+                        Some(run) if run.end == at => run.end += 1,
+                        _ => runs.push(at..at + 1),
+                    }
+                }
+            }
+            offset += 8;
+        }
+    }
+    Ok(runs)
+}
+
+/// How many bits differ between `a` and `b` over the first `len` bits of
+/// each range.
+fn count_differences(
+    a: &BitReader,
+    a_start: usize,
+    b: &BitReader,
+    b_start: usize,
+    len: usize,
+    order: BitOrder,
+) -> Result<u64, ReadError> {
+    let a_chunks = a.aligned_chunks(a_start..a_start + len, order, Padding::Zero, CHUNK_BYTES)?;
+    let b_chunks = b.aligned_chunks(b_start..b_start + len, order, Padding::Zero, CHUNK_BYTES)?;
+    Ok(a_chunks
+        .zip(b_chunks)
+        .map(|(x, y)| {
+            x.iter()
+                .zip(&y)
+                .map(|(x, y)| (x ^ y).count_ones() as u64)
+                .sum::<u64>()
+        })
+        .sum())
+}
+
+/// The shift of `b_start`, within `max` bits either way, at which the first
+/// `probe` bits of `a_range` and of `b` agree best, with how many of those
+/// bits still differ. Ties go to the smallest shift.
+pub fn best_shift(
+    a: &BitReader,
+    a_range: Range<usize>,
+    b: &BitReader,
+    b_start: usize,
+    max: usize,
+    probe: usize,
+    order: BitOrder,
+) -> Result<(isize, u64), ReadError> {
+    let mut best: Option<(isize, u64, usize)> = None;
+    // 0, 1, -1, 2, -2, ...
+    let shifts = (0..=max as isize).flat_map(|k| if k == 0 { vec![0] } else { vec![k, -k] });
+    for shift in shifts {
+        let Some(b_start) = b_start.checked_add_signed(shift) else {
+            continue;
+        };
+        // A shifted range may run on past the original one, up to the end
+        let len = probe
+            .min(a_range.len())
+            .min(b.len_bits().saturating_sub(b_start));
+        if len == 0 {
+            continue;
+        }
+        let differing = count_differences(a, a_range.start, b, b_start, len, order)?;
+        // Compare rates, since near the end fewer bits may be left to compare
+        let better = match best {
+            None => true,
+            Some((_, best_differing, best_len)) => {
+                (differing as u128) * (best_len as u128) < (best_differing as u128) * (len as u128)
+            }
+        };
+        if better {
+            best = Some((shift, differing, len));
+        }
+    }
+    Ok(best.map_or((0, 0), |(shift, differing, _)| (shift, differing)))
+}
@@ -0,0 +1,216 @@
+//! Checking extracted values against expected ones.
+//!
+//! An expectation is a value (`0xfeedfacf`, `-3`, `0b1010`, `"MZ"`), optionally
+//! with a `/MASK` of the bits to compare, or a range (`1..=7`, `..0x100`,
+//! `8..`). Numbers take the same separators as offsets; quoted text stands
+//! for its bytes, first byte most significant.
+
+use crate::offset::parse_signed;
+use num_bigint::{BigInt, Sign};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectError {
+    /// Not a value, `VALUE/MASK` or range; holds the text as given.
+    Invalid(String),
+    /// A mask has to be a set of bits, so can't be negative.
+    NegativeMask(String),
+}
+
+impl fmt::Display for ExpectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpectError::Invalid(s) => write!(f, "Invalid expected value '{}'", s),
+            ExpectError::NegativeMask(s) => write!(f, "Mask '{}' can't be negative", s),
+        }
+    }
+}
+
+impl std::error::Error for ExpectError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    kind: Kind,
+    /// Only these bits are compared
+    mask: Option<BigInt>,
+    /// Radix to show values in: the one the expectation was written in
+    radix: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Equal(BigInt),
+    Range {
+        start: Option<BigInt>,
+        end: Option<BigInt>,
+        inclusive: bool,
+    },
+}
+
+impl Expectation {
+    /// Compare only the bits set in `mask` (a number, as for values), on
+    /// top of any `/MASK` given.
+    pub fn with_mask(mut self, mask: &str) -> Result<Self, ExpectError> {
+        let (value, _) = parse_value(mask)?;
+        if value.sign() == Sign::Minus {
+            return Err(ExpectError::NegativeMask(mask.trim().to_string()));
+        }
+        let mask = value;
+        self.mask = Some(match self.mask {
+            Some(own) => own & &mask,
+            None => mask.clone(),
+        });
+        if let Kind::Equal(value) = &mut self.kind {
+            *value &= mask;
+        }
+        Ok(self)
+    }
+
+    /// The part of `value` that is compared.
+    pub fn masked(&self, value: &BigInt) -> BigInt {
+        match &self.mask {
+            Some(mask) => value & mask,
+            None => value.clone(),
+        }
+    }
+
+    /// Whether `value` meets the expectation.
+    pub fn matches(&self, value: &BigInt) -> bool {
+        let value = self.masked(value);
+        match &self.kind {
+            Kind::Equal(expected) => value == *expected,
+            Kind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                start.as_ref().is_none_or(|start| value >= *start)
+                    && end.as_ref().is_none_or(|end| {
+                        if *inclusive { value <= *end } else { value < *end }
+                    })
+            }
+        }
+    }
+
+    /// Format `value` in the radix the expectation was written in.
+    pub fn format_value(&self, value: &BigInt) -> String {
+        let sign = if value.sign() == Sign::Minus { "-" } else { "" };
+        let magnitude = value.magnitude();
+        match self.radix {
+            16 => format!("{}{:#x}", sign, magnitude),
+            2 => format!("{}{:#b}", sign, magnitude),
+            _ => format!("{}{}", sign, magnitude),
+        }
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |n: &Option<BigInt>| n.as_ref().map_or(String::new(), |n| self.format_value(n));
+        match &self.kind {
+            Kind::Equal(value) => write!(f, "{}", self.format_value(value))?,
+            Kind::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(
+                f,
+                "{}{}{}",
+                show(start),
+                if *inclusive { "..=" } else { ".." },
+                show(end)
+            )?,
+        }
+        if let Some(mask) = &self.mask {
+            write!(f, " under mask {}", self.format_value(mask))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Expectation {
+    type Err = ExpectError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((start, end)) = split_range(s) {
+            let (end, inclusive) = match end.strip_prefix('=') {
+                Some(end) => (end, true),
+                None => (end, false),
+            };
+            let bound = |part: &str| -> Result<(Option<BigInt>, u32), ExpectError> {
+                let part = part.trim();
+                if part.is_empty() {
+                    return Ok((None, 10));
+                }
+                let (value, radix) = parse_value(part)?;
+                Ok((Some(value), radix))
+            };
+            let (start, start_radix) = bound(start)?;
+            let (end, end_radix) = bound(end)?;
+            if start.is_none() && end.is_none() || inclusive && end.is_none() {
+                return Err(invalid(s));
+            }
+            let radix = if start.is_some() { start_radix } else { end_radix };
+            return Ok(Expectation {
+                kind: Kind::Range {
+                    start,
+                    end,
+                    inclusive,
+                },
+                mask: None,
+                radix,
+            });
+        }
+
+        // Text can hold a `/`; only look for a mask after it
+        let (value, mask) = match s.rsplit_once('/') {
+            Some((value, mask)) if !mask.contains('"') => (value, Some(mask)),
+            _ => (s, None),
+        };
+        let (value, radix) = parse_value(value)?;
+        let mask = mask.map(|mask| parse_value(mask).map(|(mask, _)| mask)).transpose()?;
+        if mask.as_ref().is_some_and(|mask| mask.sign() == Sign::Minus) {
+            return Err(ExpectError::NegativeMask(s.to_string()));
+        }
+        Ok(Expectation {
+            kind: Kind::Equal(match &mask {
+                Some(mask) => value & mask,
+                None => value,
+            }),
+            mask,
+            radix,
+        })
+    }
+}
+
+/// Split `s` at its first `..` outside quoted text, which can hold one.
+fn split_range(s: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted && s[i..].starts_with("..") => return Some((&s[..i], &s[i + 2..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn invalid(s: &str) -> ExpectError {
+    ExpectError::Invalid(s.to_string())
+}
+
+/// A number in decimal, hex (`0x`) or binary (`0b`), possibly negative, or
+/// quoted text; also returns the radix to show values in.
+pub(crate) fn parse_value(s: &str) -> Result<(BigInt, u32), ExpectError> {
+    let s = s.trim();
+    if let Some(text) = s.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        if text.is_empty() {
+            return Err(invalid(s));
+        }
+        return Ok((BigInt::from_bytes_be(Sign::Plus, text.as_bytes()), 16));
+    }
+    parse_signed(s).ok_or_else(|| invalid(s))
+}
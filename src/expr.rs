@@ -0,0 +1,488 @@
+//! Arithmetic in offsets and lengths.
+//!
+//! Everything counts bits. Numbers take the usual literal forms (`0x1A`,
+//! `$1A`, `1Ah`, `12:3`), optionally followed by a unit: `b` (bits), `B`
+//! (bytes), `KiB`, `MiB` or `sector` (512 bytes); a unit on its own is one
+//! of it. `end` and `size` both stand for the size of the data,
+//! `@[OFFSET:BITS]` reads a pointer from it (see [`Expr::Deref`]) and
+//! `after:PATTERN` / `before:PATTERN` find a marker in it (see
+//! [`Expr::Marker`]), so expressions using them are only evaluated once the
+//! data is known.
+//! Operators are `+ - * /` with the usual precedence, and parentheses.
+
+use crate::offset::{OffsetError, parse_number, split_bits};
+use crate::reader::{BitReader, Pointer, ReadError};
+use crate::search::{Pattern, search};
+use crate::{BitOrder, ByteOrder};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expr {
+    Num(i128),
+    /// `end` or `size`: the length of the data in bits
+    Size,
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    /// `@[OFFSET:BITS]`: the unsigned field at OFFSET, as a byte offset.
+    /// Plain numbers in OFFSET count bytes, as pointers do; BITS may end in a
+    /// byte order (`32le`), and the default is big-endian.
+    Deref {
+        at: Box<Expr>,
+        bits: usize,
+        byte_order: ByteOrder,
+    },
+    /// `after:[N:]PATTERN` or `before:[N:]PATTERN`: the end or start of the
+    /// Nth match of PATTERN, counting from 1, or back from the last match if
+    /// negative. Quoted text only matches whole bytes. Bits are numbered
+    /// MSB-first in the search whatever `-e` says, as for pointers, so only
+    /// byte-aligned matches land at the same bit under `-e lsb`.
+    Marker {
+        pattern: Pattern,
+        occurrence: i64,
+        after: bool,
+        /// What was written, for errors
+        text: String,
+    },
+}
+
+/// Bits per unit, longest names first so `sectors` isn't read as `s`.
+const UNITS: &[(&str, i128)] = &[
+    ("sectors", 512 * 8),
+    ("sector", 512 * 8),
+    ("KiB", 1024 * 8),
+    ("MiB", 1024 * 1024 * 8),
+    ("B", 8),
+    ("b", 1),
+];
+
+fn error(msg: impl Into<String>) -> OffsetError {
+    OffsetError::ParseError(msg.into())
+}
+
+impl Expr {
+    pub(crate) fn parse(s: &str) -> Result<Expr, OffsetError> {
+        Self::parse_scaled(s, 1)
+    }
+
+    /// Parse with plain numbers counting `plain_scale` bits each.
+    fn parse_scaled(s: &str, plain_scale: i128) -> Result<Expr, OffsetError> {
+        // Drop separators, except in quoted text
+        let mut quoted = false;
+        let s: String = s
+            .chars()
+            .filter(|&c| {
+                quoted ^= c == '"';
+                quoted || !matches!(c, ',' | '_' | '\'')
+            })
+            .collect();
+        let mut parser = Parser {
+            src: &s,
+            pos: 0,
+            plain_scale,
+            plain: false,
+        };
+        let expr = parser.expr()?;
+        parser.skip_space();
+        if parser.pos < s.len() {
+            return Err(error(format!("unexpected '{}'", &s[parser.pos..])));
+        }
+        Ok(expr)
+    }
+
+    /// Whether the value depends on the data: its size or its contents.
+    pub(crate) fn needs_data(&self) -> bool {
+        match self {
+            Expr::Num(_) => false,
+            Expr::Size | Expr::Deref { .. } | Expr::Marker { .. } => true,
+            Expr::Neg(a) => a.needs_data(),
+            Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) | Expr::Div(a, b) => {
+                a.needs_data() || b.needs_data()
+            }
+        }
+    }
+
+    /// Evaluate an expression that doesn't need the data.
+    pub(crate) fn eval_const(&self) -> Result<i128, OffsetError> {
+        self.eval(None, &mut Vec::new()).map_err(|e| match e {
+            ReadError::Expression(e) => e,
+            e => error(e.to_string()),
+        })
+    }
+
+    /// Evaluate against `data` (`None` if not yet known), adding each pointer
+    /// followed to `chain` in the order they are read.
+    pub(crate) fn eval(
+        &self,
+        data: Option<&BitReader>,
+        chain: &mut Vec<Pointer>,
+    ) -> Result<i128, ReadError> {
+        let overflow = || ReadError::Expression(error("arithmetic overflow"));
+        let needs_data = || ReadError::Expression(error("'end' and '@[...]' need the data"));
+        Ok(match self {
+            Expr::Num(n) => *n,
+            Expr::Size => data.ok_or_else(needs_data)?.len_bits() as i128,
+            Expr::Neg(a) => a.eval(data, chain)?.checked_neg().ok_or_else(overflow)?,
+            Expr::Add(a, b) => a
+                .eval(data, chain)?
+                .checked_add(b.eval(data, chain)?)
+                .ok_or_else(overflow)?,
+            Expr::Sub(a, b) => a
+                .eval(data, chain)?
+                .checked_sub(b.eval(data, chain)?)
+                .ok_or_else(overflow)?,
+            Expr::Mul(a, b) => a
+                .eval(data, chain)?
+                .checked_mul(b.eval(data, chain)?)
+                .ok_or_else(overflow)?,
+            Expr::Div(a, b) => {
+                let divisor = b.eval(data, chain)?;
+                if divisor == 0 {
+                    return Err(ReadError::Expression(error("division by zero")));
+                }
+                a.eval(data, chain)?.div_euclid(divisor)
+            }
+            Expr::Deref {
+                at,
+                bits,
+                byte_order,
+            } => {
+                let reader = data.ok_or_else(needs_data)?;
+                let at = at.eval(data, chain)?;
+                let at = reader.absolute_offset(i64::try_from(at).map_err(|_| overflow())?)?;
+                let value = reader.read_with_byte_order(
+                    at..at.saturating_add(*bits),
+                    crate::BitOrder::Msb,
+                    *byte_order,
+                )?;
+                let value = u64::try_from(&value).map_err(|_| overflow())?;
+                chain.push(Pointer {
+                    at,
+                    bits: *bits,
+                    byte_order: *byte_order,
+                    value,
+                });
+                value as i128 * 8
+            }
+            Expr::Marker {
+                pattern,
+                occurrence,
+                after,
+                text,
+            } => {
+                let reader = data.ok_or_else(needs_data)?;
+                let align = if text.contains('"') { 8 } else { 1 };
+                let found = search(reader, pattern, BitOrder::Msb, align);
+                let index = if *occurrence > 0 {
+                    Some(*occurrence as usize - 1)
+                } else {
+                    found.len().checked_sub(occurrence.unsigned_abs() as usize)
+                };
+                let start = *index
+                    .and_then(|i| found.get(i))
+                    .ok_or_else(|| ReadError::MarkerNotFound {
+                        marker: text.clone(),
+                        matches: found.len(),
+                    })?;
+                (if *after { start + pattern.bits } else { start }) as i128
+            }
+        })
+    }
+}
+
+/// Split `s` at its last `:` outside any `@[...]` or quoted text.
+pub(crate) fn rsplit_colon(s: &str) -> Option<(&str, &str)> {
+    let mut depth = 0;
+    let mut quoted = false;
+    for (i, c) in s.char_indices().rev() {
+        match c {
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            ']' => depth += 1,
+            '[' => depth -= 1,
+            ':' if depth == 0 => return Some((&s[..i], &s[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split `s` at each `,` outside any `@[...]` or quoted text.
+pub(crate) fn split_commas(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            _ if quoted => {}
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Bits per plain number: 1, or 8 inside a pointer, where `@[0x3c:32]`
+    /// reads at byte 0x3c
+    plain_scale: i128,
+    /// Whether the last factor or term was made only of plain numbers, with
+    /// no units, `:bits` or symbols
+    plain: bool,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while let Some(c) = self.src[self.pos..].chars().next().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        if self.src[self.pos..].starts_with(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, OffsetError> {
+        let mut lhs = self.scaled_term()?;
+        loop {
+            if self.eat('+') {
+                lhs = Expr::Add(Box::new(lhs), Box::new(self.scaled_term()?));
+            } else if self.eat('-') {
+                lhs = Expr::Sub(Box::new(lhs), Box::new(self.scaled_term()?));
+            } else {
+                return Ok(lhs);
+            }
+        }
+    }
+
+    fn scaled_term(&mut self) -> Result<Expr, OffsetError> {
+        let term = self.term()?;
+        Ok(if self.plain && self.plain_scale != 1 {
+            Expr::Mul(Box::new(term), Box::new(Expr::Num(self.plain_scale)))
+        } else {
+            term
+        })
+    }
+
+    fn term(&mut self) -> Result<Expr, OffsetError> {
+        let mut lhs = self.unary()?;
+        let mut plain = self.plain;
+        loop {
+            if self.eat('*') {
+                lhs = Expr::Mul(Box::new(lhs), Box::new(self.unary()?));
+            } else if self.eat('/') {
+                lhs = Expr::Div(Box::new(lhs), Box::new(self.unary()?));
+            } else {
+                self.plain = plain;
+                return Ok(lhs);
+            }
+            plain &= self.plain;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, OffsetError> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.plain = false;
+        let primary = self.primary()?;
+        // A unit can also follow a parenthesised expression, or be spaced
+        // off from a hex number whose digits would swallow it (`0x1B B`)
+        let save = self.pos;
+        self.skip_space();
+        let word = self.word();
+        match UNITS.iter().find(|(name, _)| *name == word) {
+            Some(&(_, scale)) => {
+                self.plain = false;
+                Ok(Expr::Mul(Box::new(primary), Box::new(Expr::Num(scale))))
+            }
+            None => {
+                self.pos = save;
+                Ok(primary)
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, OffsetError> {
+        if self.eat('@') {
+            return self.deref();
+        }
+        self.skip_space();
+        for (keyword, after) in [("after:", true), ("before:", false)] {
+            if self.src[self.pos..].starts_with(keyword) {
+                self.pos += keyword.len();
+                return self.marker(after);
+            }
+        }
+        if self.eat('(') {
+            let inner = self.expr()?;
+            if !self.eat(')') {
+                return Err(error("missing ')'"));
+            }
+            // Already scaled, if need be
+            self.plain = false;
+            return Ok(inner);
+        }
+        self.skip_space();
+        let word = self.word();
+        match word {
+            "" => Err(error(match self.src[self.pos..].chars().next() {
+                Some(c) => format!("unexpected '{}'", c),
+                None => "expected a number".into(),
+            })),
+            "end" | "size" => Ok(Expr::Size),
+            // A bare unit is one of it, as in `3*sector`
+            _ => match UNITS.iter().find(|(name, _)| *name == word) {
+                Some(&(_, scale)) => Ok(Expr::Num(scale)),
+                None => {
+                    let (value, plain) = literal(word)?;
+                    self.plain = plain;
+                    Ok(Expr::Num(value))
+                }
+            },
+        }
+    }
+
+    /// The `[OFFSET:BITS]` after an `@`.
+    fn deref(&mut self) -> Result<Expr, OffsetError> {
+        let usage = || error("expected @[OFFSET:BITS]");
+        if !self.eat('[') {
+            return Err(usage());
+        }
+        let rest = &self.src[self.pos..];
+        let mut depth = 0;
+        let len = rest
+            .find(|c| match c {
+                '[' => {
+                    depth += 1;
+                    false
+                }
+                ']' if depth == 0 => true,
+                ']' => {
+                    depth -= 1;
+                    false
+                }
+                _ => false,
+            })
+            .ok_or_else(|| error("missing ']'"))?;
+        let (at, width) = rsplit_colon(&rest[..len]).ok_or_else(usage)?;
+        self.pos += len + 1;
+
+        let width = width.trim();
+        let digits = width.find(|c: char| !c.is_ascii_digit()).unwrap_or(width.len());
+        let bits: usize = width[..digits].parse().map_err(|_| usage())?;
+        if !(1..=64).contains(&bits) {
+            return Err(error("pointers must be 1-64 bits"));
+        }
+        let byte_order = match width[digits..].trim() {
+            "" => ByteOrder::Big,
+            name => name.parse::<ByteOrder>().map_err(error)?,
+        };
+        Ok(Expr::Deref {
+            at: Box::new(Expr::parse_scaled(at, 8)?),
+            bits,
+            byte_order,
+        })
+    }
+
+    /// The `[N:]PATTERN` after `after:` or `before:`. The pattern runs to the
+    /// next operator other than `/`, which it uses for masks.
+    fn marker(&mut self, after: bool) -> Result<Expr, OffsetError> {
+        let start = self.pos;
+        let rest = &self.src[self.pos..];
+        let number = rest.strip_prefix('-').unwrap_or(rest);
+        let digits = number.find(|c: char| !c.is_ascii_digit()).unwrap_or(number.len());
+        let mut occurrence = 1;
+        if digits > 0 && number[digits..].starts_with(':') {
+            let len = rest.len() - number.len() + digits;
+            occurrence = rest[..len]
+                .parse()
+                .map_err(|_| error("invalid occurrence"))?;
+            if occurrence == 0 {
+                return Err(error("occurrences count from 1, or from -1 for the last"));
+            }
+            self.pos += len + 1;
+        }
+
+        let rest = &self.src[self.pos..];
+        let len = match rest.strip_prefix('"') {
+            Some(text) => text.find('"').ok_or_else(|| error("missing '\"'"))? + 2,
+            None => rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '?' || c == '/'))
+                .unwrap_or(rest.len()),
+        };
+        let pattern = rest[..len]
+            .parse::<Pattern>()
+            .map_err(|e| OffsetError::ParseError(e.to_string()))?;
+        self.pos += len;
+        Ok(Expr::Marker {
+            pattern,
+            occurrence,
+            after,
+            text: format!(
+                "{}:{}",
+                if after { "after" } else { "before" },
+                &self.src[start..self.pos]
+            ),
+        })
+    }
+
+    /// A run of characters that can make up a literal, symbol or unit,
+    /// including a `:bits` / `.bits` suffix.
+    fn word(&mut self) -> &str {
+        let rest = &self.src[self.pos..];
+        let mut len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '$'))
+            .unwrap_or(rest.len());
+        if let Some(bits) = rest[len..].strip_prefix([':', '.'])
+            && bits.starts_with(|c: char| c.is_ascii_digit())
+        {
+            len += 1 + bits.find(|c: char| !c.is_ascii_digit()).unwrap_or(bits.len());
+        }
+        self.pos += len;
+        &rest[..len]
+    }
+}
+
+/// A number in bits: a plain literal counts bits, `bytes:bits` bytes and
+/// bits, and a unit suffix scales it. Also says whether it was plain.
+fn literal(word: &str) -> Result<(i128, bool), OffsetError> {
+    let (num_str, bits_part) = split_bits(word)?;
+    let value = match parse_number(num_str) {
+        Ok(n) => n as i128,
+        Err(e) => {
+            if bits_part.is_some() {
+                return Err(e);
+            }
+            let Some((digits, scale)) = UNITS
+                .iter()
+                .find_map(|(name, scale)| Some((num_str.strip_suffix(name)?, *scale)))
+                .filter(|(digits, _)| !digits.is_empty())
+            else {
+                return Err(e);
+            };
+            return Ok((parse_number(digits)? as i128 * scale, false));
+        }
+    };
+    Ok(match bits_part {
+        Some(bits) => (value * 8 + bits as i128, false),
+        None => (value, true),
+    })
+}
@@ -0,0 +1,83 @@
+//! Turning a run of bits into an unsigned integer.
+
+use crate::ByteOrder;
+use bitvec::domain::{BitDomain, Domain};
+use bitvec::prelude::*;
+use num_bigint::BigUint;
+
+/// Interpret `bits` as a big-endian unsigned integer: the first bit of the
+/// slice is the most significant bit of the result.
+pub fn extract_bits_to_biguint(bits: &BitSlice<u8, Msb0>) -> BigUint {
+    if bits.len() <= 128 {
+        return BigUint::from(extract_bits_to_u128(bits));
+    }
+    // Copy the whole bytes in the middle straight across, then shift in the
+    // partial bytes at either end
+    match bits.bit_domain() {
+        BitDomain::Enclave(_) => unreachable!("more than 128 bits span several bytes"),
+        BitDomain::Region { head, body, tail } => {
+            let mut value = BigUint::from(extract_bits_to_u128(head));
+            value <<= body.len();
+            value |= BigUint::from_bytes_be(whole_bytes(bits));
+            value <<= tail.len();
+            value | BigUint::from(extract_bits_to_u128(tail))
+        }
+    }
+}
+
+/// Interpret `bits` as a little-endian unsigned integer: the first bit of
+/// the slice is the least significant bit of the result.
+pub fn extract_bits_to_biguint_lsb(bits: &BitSlice<u8, Lsb0>) -> BigUint {
+    if bits.len() <= 128 {
+        return BigUint::from(extract_bits_to_u128_lsb(bits));
+    }
+    match bits.bit_domain() {
+        BitDomain::Enclave(_) => unreachable!("more than 128 bits span several bytes"),
+        BitDomain::Region { head, body, tail } => {
+            let mut value = BigUint::from(extract_bits_to_u128_lsb(tail));
+            value <<= body.len();
+            value |= BigUint::from_bytes_le(whole_bytes(bits));
+            value <<= head.len();
+            value | BigUint::from(extract_bits_to_u128_lsb(head))
+        }
+    }
+}
+
+/// The bytes wholly covered by `bits`, which is the `body` of its
+/// [`BitDomain`].
+fn whole_bytes<O: BitOrder>(bits: &BitSlice<u8, O>) -> &[u8] {
+    match bits.domain() {
+        Domain::Enclave(_) => &[],
+        Domain::Region { body, .. } => body,
+    }
+}
+
+/// [`extract_bits_to_biguint`] for at most 128 bits, without allocating.
+pub fn extract_bits_to_u128(bits: &BitSlice<u8, Msb0>) -> u128 {
+    assert!(bits.len() <= 128, "{} bits do not fit a u128", bits.len());
+    if bits.is_empty() { 0 } else { bits.load_be::<u128>() }
+}
+
+/// [`extract_bits_to_biguint_lsb`] for at most 128 bits, without allocating.
+pub fn extract_bits_to_u128_lsb(bits: &BitSlice<u8, Lsb0>) -> u128 {
+    assert!(bits.len() <= 128, "{} bits do not fit a u128", bits.len());
+    if bits.is_empty() { 0 } else { bits.load_le::<u128>() }
+}
+
+/// Rearrange `bytes`, given in stream order, into most-significant-first
+/// order. The length must be a multiple of [`ByteOrder::unit_bytes`].
+pub fn reorder_bytes(bytes: &mut [u8], order: ByteOrder) {
+    match order {
+        ByteOrder::Big => {}
+        ByteOrder::Little => bytes.reverse(),
+        ByteOrder::Pdp => bytes.chunks_exact_mut(2).for_each(|word| word.swap(0, 1)),
+        ByteOrder::WordSwap16 | ByteOrder::WordSwap32 | ByteOrder::WordSwap64 => {
+            // Reversing everything then each word restores the bytes within
+            // words while reversing the word order
+            bytes.reverse();
+            bytes
+                .chunks_exact_mut(order.unit_bytes())
+                .for_each(|word| word.reverse());
+        }
+    }
+}
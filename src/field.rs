@@ -0,0 +1,113 @@
+//! Lists of fields to read in one pass.
+//!
+//! A field list is comma-separated; each entry is `OFFSET:LENGTH`, or just
+//! `LENGTH` to start where the previous field ended (the first such field
+//! starts at bit 0). The split is at the last `:`, so offsets keep their
+//! `bytes:bits` form (`0x10:3:8`); write byte+bit lengths with a dot
+//! (`0x10:0:2.4`). Colons inside a pointer (`@[0x3c:32le]:8`) don't count.
+//!
+//! An entry can also be a range: `START..END` up to END, `START..=END`
+//! through it, and `START..` to the end of the data. Leaving out START
+//! continues from the previous field.
+//!
+//! Commas inside quoted text or `@[...]` don't split the list, but a comma
+//! can't group digits in one: `8,100` would be either a 8100-bit field or
+//! two fields, so it's rejected. Group with `_` or `'`, or write `8, 100`
+//! for two fields.
+
+use crate::expr::{rsplit_colon, split_commas};
+use crate::offset::{Length, Offset, OffsetError};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSpec {
+    /// `None` continues from the end of the previous field.
+    pub offset: Option<Offset>,
+    pub length: Length,
+}
+
+impl FieldSpec {
+    pub fn new(offset: Offset, length: Length) -> Self {
+        FieldSpec {
+            offset: Some(offset),
+            length,
+        }
+    }
+
+    /// A field that continues where the previous one ended.
+    pub fn chained(length: Length) -> Self {
+        FieldSpec {
+            offset: None,
+            length,
+        }
+    }
+
+    /// The bit the field ends at, if that's known without the data: its
+    /// offset counts from the start (or it continues from `cursor`) and
+    /// neither uses `end`, `size`, a pointer or a marker.
+    pub fn fixed_end(&self, cursor: usize) -> Option<usize> {
+        let start = match &self.offset {
+            Some(offset) if offset.is_negative || offset.needs_data() => return None,
+            Some(offset) => offset.to_bits() as u64,
+            None => cursor as u64,
+        };
+        self.length.fixed_end(start).map(|end| end as usize)
+    }
+}
+
+impl FromStr for FieldSpec {
+    type Err = OffsetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains("..") {
+            return parse_range(s);
+        }
+        match rsplit_colon(s) {
+            Some((offset, length)) => Ok(FieldSpec::new(offset.parse()?, length.parse()?)),
+            None => Ok(FieldSpec::chained(s.parse()?)),
+        }
+    }
+}
+
+/// Parse a range such as `0x100..0x200`, `0x10:3..=0x12:5` or `0x100..`.
+pub fn parse_range(s: &str) -> Result<FieldSpec, OffsetError> {
+    let Some((start, end)) = s.split_once("..") else {
+        return Err(OffsetError::ParseError(format!("expected START..END, got '{}'", s)));
+    };
+    let (end, inclusive) = match end.strip_prefix('=') {
+        Some(end) => (end, true),
+        None => (end, false),
+    };
+    let length = if end.trim().is_empty() && !inclusive {
+        Length::rest()
+    } else {
+        Length::until(end.parse()?, inclusive)
+    };
+    Ok(if start.trim().is_empty() {
+        FieldSpec::chained(length)
+    } else {
+        FieldSpec::new(start.parse()?, length)
+    })
+}
+
+/// Parse a comma-separated field list such as `0:16,16:8,4`.
+pub fn parse_field_list(s: &str) -> Result<Vec<FieldSpec>, OffsetError> {
+    let entries = split_commas(s);
+    for pair in entries.windows(2) {
+        if pair[0].ends_with(|c: char| c.is_ascii_digit()) && is_digit_group(pair[1]) {
+            return Err(OffsetError::ParseError(format!(
+                "'{},{}' looks like a grouped number; use '_' to group digits in a field list",
+                pair[0], pair[1]
+            )));
+        }
+    }
+    entries.into_iter().map(FieldSpec::from_str).collect()
+}
+
+/// Whether `entry` starts with exactly three digits, as a thousands group
+/// would.
+fn is_digit_group(entry: &str) -> bool {
+    let digits = entry.bytes().take_while(u8::is_ascii_digit).count();
+    digits == 3 && !entry[3..].starts_with(|c: char| c.is_ascii_alphanumeric())
+}
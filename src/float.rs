@@ -0,0 +1,345 @@
+//! Decoding extracted fields as IEEE 754 (and related) floating-point values.
+//!
+//! The field is taken as an unsigned integer with the sign in its top bit,
+//! exactly as [`BitReader::read`](crate::BitReader::read) returns it, so
+//! floats need not be byte-aligned.
+
+use num_bigint::BigUint;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatFormat {
+    /// IEEE 754 half precision
+    Binary16,
+    /// Brain floating point (truncated binary32)
+    Bfloat16,
+    /// IEEE 754 single precision
+    Binary32,
+    /// IEEE 754 double precision
+    Binary64,
+    /// IEEE 754 quadruple precision
+    Binary128,
+    /// Intel x87 80-bit extended precision (explicit integer bit)
+    X87Extended,
+}
+
+impl FloatFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            FloatFormat::Binary16 => "binary16",
+            FloatFormat::Bfloat16 => "bfloat16",
+            FloatFormat::Binary32 => "binary32",
+            FloatFormat::Binary64 => "binary64",
+            FloatFormat::Binary128 => "binary128",
+            FloatFormat::X87Extended => "x87 extended",
+        }
+    }
+
+    /// Look up a format by its name or short alias (`f32`, `bf16`, `f80`...).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "binary16" | "f16" => Some(FloatFormat::Binary16),
+            "bfloat16" | "bf16" => Some(FloatFormat::Bfloat16),
+            "binary32" | "f32" => Some(FloatFormat::Binary32),
+            "binary64" | "f64" => Some(FloatFormat::Binary64),
+            "binary128" | "f128" => Some(FloatFormat::Binary128),
+            "x87" | "f80" => Some(FloatFormat::X87Extended),
+            _ => None,
+        }
+    }
+
+    /// Total width in bits.
+    pub fn width(self) -> usize {
+        1 + self.exponent_bits() + self.mantissa_bits()
+    }
+
+    pub fn exponent_bits(self) -> usize {
+        match self {
+            FloatFormat::Binary16 => 5,
+            FloatFormat::Bfloat16 | FloatFormat::Binary32 => 8,
+            FloatFormat::Binary64 => 11,
+            FloatFormat::Binary128 | FloatFormat::X87Extended => 15,
+        }
+    }
+
+    /// Stored significand bits, including x87's explicit integer bit.
+    pub fn mantissa_bits(self) -> usize {
+        match self {
+            FloatFormat::Binary16 => 10,
+            FloatFormat::Bfloat16 => 7,
+            FloatFormat::Binary32 => 23,
+            FloatFormat::Binary64 => 52,
+            FloatFormat::Binary128 => 112,
+            FloatFormat::X87Extended => 64,
+        }
+    }
+
+    pub fn bias(self) -> i64 {
+        (1i64 << (self.exponent_bits() - 1)) - 1
+    }
+
+    fn has_explicit_integer_bit(self) -> bool {
+        self == FloatFormat::X87Extended
+    }
+
+    /// Significant decimal digits needed to round-trip a value.
+    fn decimal_digits(self) -> usize {
+        match self {
+            FloatFormat::Binary16 => 5,
+            FloatFormat::Bfloat16 => 4,
+            FloatFormat::Binary32 => 9,
+            FloatFormat::Binary64 => 17,
+            FloatFormat::Binary128 => 36,
+            FloatFormat::X87Extended => 21,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FloatClass {
+    Zero,
+    Subnormal,
+    Normal,
+    Infinite,
+    Nan { quiet: bool, payload: BigUint },
+    /// x87 encodings the FPU rejects (unnormals, pseudo-NaNs and the like)
+    Invalid,
+}
+
+impl fmt::Display for FloatClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FloatClass::Zero => write!(f, "zero"),
+            FloatClass::Subnormal => write!(f, "subnormal"),
+            FloatClass::Normal => write!(f, "normal"),
+            FloatClass::Infinite => write!(f, "infinite"),
+            FloatClass::Nan { quiet, payload } => write!(
+                f,
+                "{} NaN (payload {:#x})",
+                if *quiet { "quiet" } else { "signaling" },
+                payload
+            ),
+            FloatClass::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+/// A float split into its stored fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FloatParts {
+    pub format: FloatFormat,
+    pub sign: bool,
+    /// Biased exponent, as stored
+    pub exponent: u32,
+    /// Stored significand bits (with the integer bit for x87)
+    pub mantissa: BigUint,
+    pub class: FloatClass,
+}
+
+/// Split the low `format.width()` bits of `value` into sign, exponent and
+/// mantissa and classify the result.
+pub fn decode_float(value: &BigUint, format: FloatFormat) -> FloatParts {
+    let mant_bits = format.mantissa_bits();
+    let exp_bits = format.exponent_bits();
+    let mantissa = value & ((BigUint::from(1u8) << mant_bits) - 1u8);
+    let exponent = ((value >> mant_bits) & BigUint::from((1u32 << exp_bits) - 1))
+        .iter_u32_digits()
+        .next()
+        .unwrap_or(0);
+    let sign = value.bit((mant_bits + exp_bits) as u64);
+    let max_exp = (1u32 << exp_bits) - 1;
+
+    let class = if format.has_explicit_integer_bit() {
+        let integer_bit = mantissa.bit(63);
+        let fraction = &mantissa & ((BigUint::from(1u8) << 63u32) - 1u8);
+        if exponent == 0 {
+            if mantissa == BigUint::ZERO {
+                FloatClass::Zero
+            } else {
+                // Includes pseudo-denormals (integer bit set), which the FPU
+                // still accepts
+                FloatClass::Subnormal
+            }
+        } else if !integer_bit {
+            FloatClass::Invalid
+        } else if exponent == max_exp {
+            if fraction == BigUint::ZERO {
+                FloatClass::Infinite
+            } else {
+                let quiet = fraction.bit(62);
+                let payload = fraction & ((BigUint::from(1u8) << 62u32) - 1u8);
+                FloatClass::Nan { quiet, payload }
+            }
+        } else {
+            FloatClass::Normal
+        }
+    } else if exponent == 0 {
+        if mantissa == BigUint::ZERO {
+            FloatClass::Zero
+        } else {
+            FloatClass::Subnormal
+        }
+    } else if exponent == max_exp {
+        if mantissa == BigUint::ZERO {
+            FloatClass::Infinite
+        } else {
+            let quiet = mantissa.bit(mant_bits as u64 - 1);
+            let payload = &mantissa & ((BigUint::from(1u8) << (mant_bits - 1)) - 1u8);
+            FloatClass::Nan { quiet, payload }
+        }
+    } else {
+        FloatClass::Normal
+    };
+
+    FloatParts {
+        format,
+        sign,
+        exponent,
+        mantissa,
+        class,
+    }
+}
+
+impl FloatParts {
+    /// Exponent with the bias removed (`None` for zero, infinity, NaN and
+    /// invalid encodings).
+    pub fn unbiased_exponent(&self) -> Option<i64> {
+        match self.class {
+            FloatClass::Normal => Some(self.exponent as i64 - self.format.bias()),
+            FloatClass::Subnormal => Some(1 - self.format.bias()),
+            _ => None,
+        }
+    }
+
+    /// The finite value as `significand * 2^exponent`, with an integer
+    /// significand.
+    pub fn significand_and_exponent(&self) -> Option<(BigUint, i64)> {
+        let unbiased = self.unbiased_exponent()?;
+        let mant_bits = self.format.mantissa_bits() as i64;
+        if self.format.has_explicit_integer_bit() {
+            Some((self.mantissa.clone(), unbiased - (mant_bits - 1)))
+        } else if self.class == FloatClass::Subnormal {
+            Some((self.mantissa.clone(), unbiased - mant_bits))
+        } else {
+            let implicit = BigUint::from(1u8) << mant_bits as usize;
+            Some((implicit | &self.mantissa, unbiased - mant_bits))
+        }
+    }
+
+    /// Convert to `f64`, rounding if the format is wider.
+    pub fn to_f64(&self) -> f64 {
+        let sign = if self.sign { -1.0 } else { 1.0 };
+        match &self.class {
+            FloatClass::Zero => sign * 0.0,
+            FloatClass::Infinite => sign * f64::INFINITY,
+            FloatClass::Nan { .. } | FloatClass::Invalid => f64::NAN,
+            _ => {
+                let Some((m, e)) = self.significand_and_exponent() else {
+                    return f64::NAN;
+                };
+                // Keep the top 64 bits of the significand; scale in two steps
+                // so huge and tiny exponents don't overflow powi
+                let shift = (m.bits() as i64 - 64).max(0);
+                let top = (&m >> shift as usize).iter_u64_digits().next().unwrap_or(0);
+                let e = e + shift;
+                let half = (e / 2) as i32;
+                sign * top as f64 * 2f64.powi(half) * 2f64.powi(e as i32 - half)
+            }
+        }
+    }
+}
+
+impl fmt::Display for FloatParts {
+    /// Formats the value in decimal: shortest round-trip form for binary32
+    /// and binary64, enough significant digits to round-trip for the others.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.sign { "-" } else { "" };
+        match &self.class {
+            FloatClass::Zero => return write!(f, "{}0", sign),
+            FloatClass::Infinite => return write!(f, "{}inf", sign),
+            FloatClass::Nan { .. } | FloatClass::Invalid => return write!(f, "NaN"),
+            _ => {}
+        }
+        match self.format {
+            FloatFormat::Binary32 | FloatFormat::Binary64 => {
+                // Same cut-over to scientific notation as format_decimal
+                let v = self.to_f64();
+                let positional = (1e-5..10f64.powi(self.format.decimal_digits() as i32))
+                    .contains(&v.abs());
+                match (self.format, positional) {
+                    (FloatFormat::Binary32, true) => write!(f, "{}", v as f32),
+                    (FloatFormat::Binary32, false) => write!(f, "{:e}", v as f32),
+                    (_, true) => write!(f, "{}", v),
+                    (_, false) => write!(f, "{:e}", v),
+                }
+            }
+            _ => {
+                let (m, e) = self.significand_and_exponent().unwrap_or_default();
+                write!(
+                    f,
+                    "{}{}",
+                    sign,
+                    format_decimal(&m, e, self.format.decimal_digits())
+                )
+            }
+        }
+    }
+}
+
+/// Format `m * 2^e` with `digits` significant decimal digits, correctly
+/// rounded, trimming trailing zeros.
+fn format_decimal(m: &BigUint, e: i64, digits: usize) -> String {
+    if *m == BigUint::ZERO {
+        return "0".into();
+    }
+    let ten = BigUint::from(10u8);
+    let low = ten.pow(digits as u32 - 1);
+    let high = ten.pow(digits as u32);
+
+    // Estimate the decimal exponent, then correct it if the estimate is off
+    let mut k =
+        ((m.bits() as f64 - 1.0 + e as f64) * std::f64::consts::LOG10_2).floor() as i64;
+    let q = loop {
+        let scale = k - digits as i64 + 1;
+        let mut num = m << e.max(0) as usize;
+        let mut den = BigUint::from(1u8) << (-e).max(0) as usize;
+        if scale >= 0 {
+            den *= ten.pow(scale as u32);
+        } else {
+            num *= ten.pow((-scale) as u32);
+        }
+        let (mut q, r) = (&num / &den, &num % &den);
+        // Round half to even
+        let twice = r << 1u32;
+        if twice > den || (twice == den && q.bit(0)) {
+            q += 1u8;
+        }
+        if q >= high {
+            k += 1;
+        } else if q < low {
+            k -= 1;
+        } else {
+            break q;
+        }
+    };
+
+    let s = q.to_string();
+    let s = s.trim_end_matches('0');
+    let (first, rest) = s.split_at(1);
+    if (-5..digits as i64).contains(&k) {
+        if k < 0 {
+            format!("0.{}{}", "0".repeat((-k - 1) as usize), s)
+        } else {
+            let k = k as usize;
+            if s.len() > k + 1 {
+                format!("{}.{}", &s[..=k], &s[k + 1..])
+            } else {
+                format!("{}{}", s, "0".repeat(k + 1 - s.len()))
+            }
+        }
+    } else if rest.is_empty() {
+        format!("{}e{}", first, k)
+    } else {
+        format!("{}.{}e{}", first, rest, k)
+    }
+}
@@ -0,0 +1,120 @@
+//! Opening the data to read: memory-mapped when possible, buffered otherwise.
+
+use crate::archive::{Member, read_member};
+use crate::decompress::{Compression, decompress};
+use memmap2::{Mmap, MmapOptions};
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::{Deref, Range};
+
+/// The bytes of an input file, derefing to `[u8]` for a
+/// [`BitReader`](crate::BitReader).
+///
+/// Regular files are memory-mapped so that huge files cost nothing up front.
+/// Anything that can't be mapped (stdin, pipes, FIFOs, sockets, devices,
+/// procfs files that report a size of zero) is read into memory: in full,
+/// which keeps negative offsets working, or only as far as the caller needs,
+/// so endless sources such as `/dev/zero` can be read too.
+#[derive(Debug)]
+pub enum Input {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+    /// Part of another input, such as an archive member stored as is
+    Slice { source: Box<Input>, range: Range<usize> },
+}
+
+impl Input {
+    /// Open `path`, or standard input if it is `-`.
+    pub fn open(path: &str) -> io::Result<Self> {
+        Self::open_up_to(path, None)
+    }
+
+    /// Open `path` as [`open`](Self::open) does, but read at most `limit`
+    /// bytes of it if it can't be mapped.
+    pub fn open_up_to(path: &str, limit: Option<usize>) -> io::Result<Self> {
+        let read = |source: Box<dyn Read>| match limit {
+            Some(limit) => Self::read_all(source.take(limit as u64)),
+            None => Self::read_all(source),
+        };
+        if path == "-" {
+            return read(Box::new(io::stdin().lock()));
+        }
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if metadata.is_file() && metadata.len() > 0 {
+            // Safety: as with any mapped file, another process truncating or
+            // rewriting it while we read gives undefined results
+            if let Ok(mmap) = unsafe { MmapOptions::new().map(&file) } {
+                return Ok(Input::Mapped(mmap));
+            }
+        }
+        read(Box::new(file))
+    }
+
+    /// Read everything from `source` into memory.
+    pub fn read_all(mut source: impl Read) -> io::Result<Self> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+        Ok(Input::Buffered(data))
+    }
+
+    /// Replace the input with its decompressed contents, detecting the
+    /// format from its magic number unless one is given. Returns the format
+    /// used.
+    pub fn decompress(&mut self, format: Option<Compression>) -> io::Result<Compression> {
+        let format = match format.or_else(|| Compression::detect(self)) {
+            Some(format) => format,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not gzip, bzip2, xz or zstd data",
+                ));
+            }
+        };
+        *self = Input::Buffered(decompress(self, format)?);
+        Ok(format)
+    }
+
+    /// Narrow the input to an archive member found by
+    /// [`find_member`](crate::archive::find_member). Members stored as is
+    /// are sliced out of the archive in place; compressed ones are
+    /// decompressed into memory.
+    pub fn into_member(self, member: &Member) -> io::Result<Self> {
+        if member.compressed {
+            return Ok(Input::Buffered(read_member(&self, member)?));
+        }
+        let start = member.data_offset as usize;
+        let range = start..start + member.size as usize;
+        if range.end > self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("member '{}' runs past the end of the archive", member.name),
+            ));
+        }
+        Ok(Input::Slice {
+            source: Box::new(self),
+            range,
+        })
+    }
+
+    /// Whether the input was memory-mapped rather than read in.
+    pub fn is_mapped(&self) -> bool {
+        match self {
+            Input::Mapped(_) => true,
+            Input::Buffered(_) => false,
+            Input::Slice { source, .. } => source.is_mapped(),
+        }
+    }
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Input::Mapped(mmap) => mmap,
+            Input::Buffered(data) => data,
+            Input::Slice { source, range } => &source[range.clone()],
+        }
+    }
+}
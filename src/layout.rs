@@ -0,0 +1,568 @@
+//! Declarative structure definitions.
+//!
+//! A layout is a TOML file listing named fields in order. Each field is read
+//! where the previous one ended unless it gives its own `offset`: relative to
+//! the start of the structure, or from the end of the data if negative. An
+//! offset that depends on the data (`end`, `size`, a pointer or a marker) is a
+//! position in the whole data, as on the command line, wherever the structure
+//! starts:
+//!
+//! ```toml
+//! name = "example"
+//! byte_order = "little"          # default for all fields
+//!
+//! [enums.kind]
+//! 1 = "data"
+//! 2 = "index"
+//!
+//! [[field]]
+//! name = "kind"
+//! bits = 8
+//! enum = "kind"
+//!
+//! [[field]]
+//! name = "count"
+//! bits = 8
+//!
+//! [[field]]
+//! name = "delta"
+//! bits = 12
+//! count = "count"                # array sized by an earlier field
+//! signed = "twos"
+//! bit_order = "msb"
+//! byte_order = "big"
+//!
+//! [[field]]
+//! name = "checksum"
+//! bits = 16
+//! if = "kind == 2"               # only present for index records
+//! display = "decimal"
+//! ```
+//!
+//! Field keys: `name`, `bits` (a [`Length`]), `offset` (an [`Offset`]),
+//! `bit_order`, `byte_order`, `signed` (a [`SignedEncoding`]), `float` (a
+//! [`FloatFormat`] name), `display` (`hex`, `decimal`, `binary`, `ascii`),
+//! `enum`, `count` (a number or an earlier field's name) and `if` (`NAME`,
+//! or `NAME OP NUMBER` with `==`, `!=`, `<`, `<=`, `>`, `>=` or `&`).
+
+use crate::float::{FloatFormat, FloatParts, decode_float};
+use crate::offset::{Length, Offset, parse_number, parse_signed};
+use crate::reader::{BitReader, ReadError};
+use crate::signed::{SignedEncoding, to_signed};
+use crate::{BitOrder, ByteOrder};
+use num_bigint::{BigInt, BigUint, Sign};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// The definition file isn't valid TOML or has unknown keys.
+    Parse(String),
+    /// A field's settings are invalid.
+    Invalid { field: String, msg: String },
+    /// A count or condition names a field that has no usable value yet.
+    UnknownField { field: String, name: String },
+    /// Reading the field failed.
+    Read { field: String, source: ReadError },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Parse(msg) => write!(f, "Invalid structure definition: {}", msg),
+            LayoutError::Invalid { field, msg } => write!(f, "Field '{}': {}", field, msg),
+            LayoutError::UnknownField { field, name } => write!(
+                f,
+                "Field '{}' refers to '{}', which is not an earlier scalar field",
+                field, name
+            ),
+            LayoutError::Read { field, source } => write!(f, "Field '{}': {}", field, source),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// How an integer field's value is shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum FieldDisplay {
+    Hex,
+    Decimal,
+    Binary,
+    Ascii,
+}
+
+/// `hex`, `decimal`, `binary` or `ascii`.
+impl FromStr for FieldDisplay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_name(
+            s,
+            "display",
+            &[
+                ("hex", FieldDisplay::Hex),
+                ("decimal", FieldDisplay::Decimal),
+                ("binary", FieldDisplay::Binary),
+                ("ascii", FieldDisplay::Ascii),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Count {
+    Fixed(u64),
+    Field(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Any of the mask's bits set
+    And,
+}
+
+/// `if` condition: a field is non-zero, or compares against a number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub field: String,
+    pub compare: Option<(CompareOp, BigInt)>,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(op_start) = s.find(['=', '!', '<', '>', '&']) else {
+            return Ok(Condition {
+                field: s.trim().to_string(),
+                compare: None,
+            });
+        };
+        let (field, rest) = s.split_at(op_start);
+        let (op, number) = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+            ("&", CompareOp::And),
+        ]
+        .iter()
+        .find_map(|(token, op)| rest.strip_prefix(token).map(|number| (*op, number)))
+        .ok_or_else(|| format!("Invalid condition '{}'", s))?;
+        let (number, _) = parse_signed(number).ok_or_else(|| format!("Invalid number in '{}'", s))?;
+        Ok(Condition {
+            field: field.trim().to_string(),
+            compare: Some((op, number)),
+        })
+    }
+}
+
+impl Condition {
+    fn holds(&self, value: &BigInt) -> bool {
+        match &self.compare {
+            None => *value != BigInt::ZERO,
+            Some((op, n)) => match op {
+                CompareOp::Eq => value == n,
+                CompareOp::Ne => value != n,
+                CompareOp::Lt => value < n,
+                CompareOp::Le => value <= n,
+                CompareOp::Gt => value > n,
+                CompareOp::Ge => value >= n,
+                CompareOp::And => value & n != BigInt::ZERO,
+            },
+        }
+    }
+}
+
+/// One field of a [`Layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutField {
+    pub name: String,
+    pub bits: Length,
+    pub offset: Option<Offset>,
+    pub bit_order: Option<BitOrder>,
+    pub byte_order: Option<ByteOrder>,
+    pub signed: Option<SignedEncoding>,
+    pub float: Option<FloatFormat>,
+    pub display: Option<FieldDisplay>,
+    pub enum_name: Option<String>,
+    pub count: Option<Count>,
+    pub condition: Option<Condition>,
+}
+
+/// A parsed structure definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub name: Option<String>,
+    pub bit_order: BitOrder,
+    pub byte_order: Option<ByteOrder>,
+    pub enums: BTreeMap<String, BTreeMap<BigInt, String>>,
+    pub fields: Vec<LayoutField>,
+}
+
+/// A decoded value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    Unsigned(BigUint),
+    Signed(BigInt),
+    Float(FloatParts),
+}
+
+/// One field (or array element) read by [`Layout::decode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedField {
+    /// Field name, with `[i]` appended for array elements
+    pub name: String,
+    pub range: Range<usize>,
+    pub value: FieldValue,
+    pub display: FieldDisplay,
+    /// Enum label for the value, if any
+    pub label: Option<String>,
+}
+
+impl fmt::Display for DecodedField {
+    /// Formats the value (not the name or offset) with its enum label.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            FieldValue::Float(parts) => write!(f, "{}", parts)?,
+            FieldValue::Unsigned(v) => write_integer(f, &BigInt::from(v.clone()), self)?,
+            FieldValue::Signed(v) => write_integer(f, v, self)?,
+        }
+        if let Some(label) = &self.label {
+            write!(f, " ({})", label)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_integer(f: &mut fmt::Formatter<'_>, v: &BigInt, field: &DecodedField) -> fmt::Result {
+    match field.display {
+        FieldDisplay::Hex => write!(f, "{:#x}", v),
+        FieldDisplay::Decimal => write!(f, "{}", v),
+        FieldDisplay::Binary => write!(f, "{:#b}", v),
+        FieldDisplay::Ascii => {
+            // The raw bits, padded to whole bytes
+            let (_, mut bytes) = v.to_bytes_be();
+            let num_bytes = field.range.len().div_ceil(8);
+            while bytes.len() < num_bytes {
+                bytes.insert(0, 0);
+            }
+            write!(f, "\"{}\"", bytes.escape_ascii())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawLayout {
+    name: Option<String>,
+    bit_order: Option<String>,
+    byte_order: Option<String>,
+    #[serde(default)]
+    enums: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default, rename = "field")]
+    fields: Vec<RawField>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawField {
+    name: String,
+    bits: NumberOrString,
+    offset: Option<NumberOrString>,
+    bit_order: Option<String>,
+    byte_order: Option<String>,
+    signed: Option<String>,
+    float: Option<String>,
+    display: Option<String>,
+    #[serde(rename = "enum")]
+    enum_name: Option<String>,
+    count: Option<NumberOrString>,
+    #[serde(rename = "if")]
+    condition: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(i64),
+    String(String),
+}
+
+impl NumberOrString {
+    fn as_string(&self) -> String {
+        match self {
+            NumberOrString::Number(n) => n.to_string(),
+            NumberOrString::String(s) => s.clone(),
+        }
+    }
+}
+
+fn parse_enum<T: FromStr<Err = String>>(field: &str, s: &str) -> Result<T, LayoutError> {
+    s.parse().map_err(|msg| LayoutError::Invalid {
+        field: field.to_string(),
+        msg,
+    })
+}
+
+impl FromStr for Layout {
+    type Err = LayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let raw: RawLayout = toml::from_str(s).map_err(|e| LayoutError::Parse(e.to_string()))?;
+        let top = raw.name.as_deref().unwrap_or("(layout)");
+
+        let bit_order = match &raw.bit_order {
+            Some(s) => parse_enum(top, s)?,
+            None => BitOrder::Msb,
+        };
+        let byte_order = match &raw.byte_order {
+            Some(s) => Some(parse_enum(top, s)?),
+            None => None,
+        };
+
+        let mut enums = BTreeMap::new();
+        for (name, values) in raw.enums {
+            let mut map = BTreeMap::new();
+            for (key, label) in values {
+                let (value, _) = parse_signed(&key).ok_or_else(|| LayoutError::Invalid {
+                    field: name.clone(),
+                    msg: format!("invalid enum value '{}'", key),
+                })?;
+                map.insert(value, label);
+            }
+            enums.insert(name, map);
+        }
+
+        let mut fields = Vec::new();
+        for f in raw.fields {
+            let invalid = |msg: String| LayoutError::Invalid {
+                field: f.name.clone(),
+                msg,
+            };
+            let bits: Length = f
+                .bits
+                .as_string()
+                .parse()
+                .map_err(|e| invalid(format!("bits: {}", e)))?;
+            if bits.needs_data() {
+                return Err(invalid("bits can't depend on the data".into()));
+            }
+            if bits.to_bits() == 0 {
+                return Err(invalid("bits must be at least 1".into()));
+            }
+            let offset = match &f.offset {
+                Some(o) => Some(
+                    o.as_string()
+                        .parse::<Offset>()
+                        .map_err(|e| invalid(format!("offset: {}", e)))?,
+                ),
+                None => None,
+            };
+            let float = match &f.float {
+                Some(s) => {
+                    let format = FloatFormat::from_name(s)
+                        .ok_or_else(|| invalid(format!("unknown float format '{}'", s)))?;
+                    if format.width() as u64 != bits.to_bits() {
+                        return Err(invalid(format!(
+                            "{} needs {} bits",
+                            format.name(),
+                            format.width()
+                        )));
+                    }
+                    Some(format)
+                }
+                None => None,
+            };
+            if let Some(name) = &f.enum_name
+                && !enums.contains_key(name)
+            {
+                return Err(invalid(format!("unknown enum '{}'", name)));
+            }
+            let count = match &f.count {
+                Some(NumberOrString::Number(n)) => Some(Count::Fixed(
+                    u64::try_from(*n).map_err(|_| invalid("negative count".into()))?,
+                )),
+                // An earlier field's name wins over a number, since names
+                // like `ab` or `dh` also read as hex
+                Some(NumberOrString::String(s)) => {
+                    let name = s.trim();
+                    Some(match parse_number(name) {
+                        Ok(n) if !fields.iter().any(|f: &LayoutField| f.name == name) => {
+                            Count::Fixed(n)
+                        }
+                        _ => Count::Field(name.to_string()),
+                    })
+                }
+                None => None,
+            };
+            let condition = match &f.condition {
+                Some(c) => Some(c.parse::<Condition>().map_err(invalid)?),
+                None => None,
+            };
+            fields.push(LayoutField {
+                bit_order: match &f.bit_order {
+                    Some(s) => Some(parse_enum(&f.name, s)?),
+                    None => None,
+                },
+                byte_order: match &f.byte_order {
+                    Some(s) => Some(parse_enum(&f.name, s)?),
+                    None => None,
+                },
+                signed: match &f.signed {
+                    Some(s) => Some(parse_enum(&f.name, s)?),
+                    None => None,
+                },
+                display: match &f.display {
+                    Some(s) => Some(parse_enum(&f.name, s)?),
+                    None => None,
+                },
+                name: f.name,
+                bits,
+                offset,
+                float,
+                enum_name: f.enum_name,
+                count,
+                condition,
+            });
+        }
+
+        Ok(Layout {
+            name: raw.name,
+            bit_order,
+            byte_order,
+            enums,
+            fields,
+        })
+    }
+}
+
+impl Layout {
+    /// Decode the structure starting at bit `base`.
+    ///
+    /// Fields whose condition is false are skipped; arrays produce one
+    /// [`DecodedField`] per element.
+    pub fn decode(&self, reader: &BitReader, base: usize) -> Result<Vec<DecodedField>, LayoutError> {
+        // Scalar values seen so far, for counts and conditions
+        let mut values: HashMap<&str, BigInt> = HashMap::new();
+        let mut cursor = base;
+        let mut decoded = Vec::new();
+
+        for field in &self.fields {
+            let lookup = |name: &str| {
+                values.get(name).ok_or_else(|| LayoutError::UnknownField {
+                    field: field.name.clone(),
+                    name: name.to_string(),
+                })
+            };
+            let read_error = |source| LayoutError::Read {
+                field: field.name.clone(),
+                source,
+            };
+
+            if let Some(condition) = &field.condition
+                && !condition.holds(lookup(&condition.field)?)
+            {
+                continue;
+            }
+
+            let start = match &field.offset {
+                Some(offset) if offset.is_negative || offset.needs_data() => {
+                    reader.resolve_offset(offset).map_err(read_error)?
+                }
+                Some(offset) => base + offset.to_bits() as usize,
+                None => cursor,
+            };
+            let count = match &field.count {
+                None => None,
+                Some(Count::Fixed(n)) => Some(*n),
+                Some(Count::Field(name)) => {
+                    let n = lookup(name)?;
+                    Some(u64::try_from(n).map_err(|_| LayoutError::Invalid {
+                        field: field.name.clone(),
+                        msg: format!("count '{}' is {}", name, n),
+                    })?)
+                }
+            };
+
+            let width = field.bits.to_bits() as usize;
+            // A count read from the data can be anything; check the whole
+            // array fits before reading any of it
+            let left = reader.len_bits().saturating_sub(start) as u64;
+            if let Some(n) = count
+                && n.checked_mul(width as u64).is_none_or(|bits| bits > left)
+            {
+                return Err(LayoutError::Invalid {
+                    field: field.name.clone(),
+                    msg: format!("{} elements of {} bits don't fit in the {} bits left", n, width, left),
+                });
+            }
+            let bit_order = field.bit_order.unwrap_or(self.bit_order);
+            let byte_order = field.byte_order.or(self.byte_order);
+            let mut end = start;
+            let mut scalar = None;
+            for i in 0..count.unwrap_or(1) {
+                let range = end..end + width;
+                let raw = match byte_order {
+                    Some(byte_order) => reader.read_with_byte_order(range.clone(), bit_order, byte_order),
+                    None => reader.read(range.clone(), bit_order),
+                }
+                .map_err(read_error)?;
+                end = range.end;
+
+                let (value, number) = match (field.float, field.signed) {
+                    (Some(format), _) => (FieldValue::Float(decode_float(&raw, format)), None),
+                    (None, Some(encoding)) => {
+                        let v = to_signed(&raw, width, encoding, None);
+                        (FieldValue::Signed(v.clone()), Some(v))
+                    }
+                    (None, None) => {
+                        let v = BigInt::from_biguint(Sign::Plus, raw.clone());
+                        (FieldValue::Unsigned(raw), Some(v))
+                    }
+                };
+                let label = match (&field.enum_name, &number) {
+                    (Some(name), Some(n)) => self.enums[name].get(n).cloned(),
+                    _ => None,
+                };
+                let display = field.display.unwrap_or(match (&field.signed, &field.enum_name) {
+                    (None, None) => FieldDisplay::Hex,
+                    _ => FieldDisplay::Decimal,
+                });
+                decoded.push(DecodedField {
+                    name: match count {
+                        Some(_) => format!("{}[{}]", field.name, i),
+                        None => field.name.clone(),
+                    },
+                    range,
+                    value,
+                    display,
+                    label,
+                });
+                scalar = number;
+            }
+
+            if count.is_none()
+                && let Some(number) = scalar
+            {
+                values.insert(&field.name, number);
+            }
+            cursor = end;
+        }
+
+        Ok(decoded)
+    }
+}
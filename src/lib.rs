@@ -0,0 +1,171 @@
+//! Read arbitrary-sized bitfields from byte slices at any bit offset.
+//!
+//! This is the library behind the `itty-bitty` command-line tool. The same
+//! offset/length syntax the CLI accepts can be parsed with [`Offset`] and
+//! [`Length`], resolved against the data with [`BitReader::resolve`], and the
+//! resulting range read as a [`BigUint`](num_bigint::BigUint) or a native
+//! integer. [`to_signed`] reinterprets a field in any of the common signed
+//! encodings, and [`decode_float`] as an IEEE 754 or x87 float. A [`Layout`]
+//! decodes a whole structure described in a TOML file, and [`search`] finds a
+//! [`Pattern`] at any bit offset. A [`BitWriter`] patches fields in place,
+//! and [`analyze`] gathers entropy and other statistics over a range.
+//!
+//! ```
+//! use itty_bitty::{BitOrder, BitReader};
+//!
+//! let data = b"BZh91AY&SY";
+//! let reader = BitReader::new(data);
+//! let offset = "4:0".parse().unwrap();
+//! let length = "48".parse().unwrap();
+//! let range = reader.resolve(&offset, &length).unwrap();
+//! assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 0x314159265359);
+//! ```
+
+pub mod archive;
+pub mod decompress;
+pub mod diff;
+pub mod expect;
+mod expr;
+pub mod extract;
+pub mod field;
+pub mod float;
+pub mod input;
+pub mod layout;
+pub mod offset;
+pub mod reader;
+pub mod search;
+pub mod signed;
+pub mod stats;
+pub mod writer;
+
+pub use archive::{ArchiveFormat, Member, find_member, split_member_path};
+pub use decompress::{Compression, decompress};
+pub use diff::{best_shift, diff_ranges};
+pub use expect::{ExpectError, Expectation};
+pub use extract::{
+    extract_bits_to_biguint, extract_bits_to_biguint_lsb, extract_bits_to_u128,
+    extract_bits_to_u128_lsb, reorder_bytes,
+};
+pub use field::{FieldSpec, parse_field_list, parse_range};
+pub use float::{FloatClass, FloatFormat, FloatParts, decode_float};
+pub use input::Input;
+pub use layout::{DecodedField, FieldValue, Layout, LayoutError};
+pub use offset::{Length, Offset, OffsetError, parse_signed};
+pub use reader::{AlignedChunks, BitReader, ByteChunks, Padding, Pointer, ReadError};
+pub use search::{Pattern, PatternError, search, search_range};
+pub use signed::{SignedEncoding, from_signed, to_signed};
+pub use stats::{BitStats, analyze};
+pub use writer::{
+    BitWriter, JournalEntry, WriteError, append_journal, journal_path, map_writable, parse_value,
+    read_journal, write_journal,
+};
+
+use std::str::FromStr;
+
+/// Bytes handled at a time when a whole range is streamed through
+/// [`BitReader::aligned_chunks`] or [`BitReader::byte_chunks`], as by
+/// [`diff_ranges`] and [`analyze`]. 48 KiB rather than 64 so that a chunk is
+/// a whole number of hex dump rows at every row width the command-line tool
+/// uses (8 to 64 bytes, including 12, 24 and 48).
+pub const CHUNK_BYTES: usize = 48 * 1024;
+
+/// How bits are numbered within each byte, and which end of the field is
+/// most significant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum BitOrder {
+    Msb,
+    Lsb,
+}
+
+/// How the bytes of a field map to significance, independent of [`BitOrder`].
+///
+/// Each byte of the field is first assembled from the stream with the bit
+/// order in effect; the byte order then says which of those bytes is most
+/// significant. Without an explicit byte order, `Msb` reads big-endian and
+/// `Lsb` little-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ByteOrder {
+    /// First byte is most significant
+    #[cfg_attr(feature = "cli", value(alias = "be"))]
+    Big,
+    /// First byte is least significant
+    #[cfg_attr(feature = "cli", value(alias = "le"))]
+    Little,
+    /// PDP-11 middle-endian: big-endian 16-bit words, bytes swapped within each
+    #[cfg_attr(feature = "cli", value(alias = "middle"))]
+    Pdp,
+    /// Big-endian 16-bit words stored in reverse order
+    WordSwap16,
+    /// Big-endian 32-bit words stored in reverse order
+    WordSwap32,
+    /// Big-endian 64-bit words stored in reverse order
+    WordSwap64,
+}
+
+/// Look `s` up among `names`, aliases included, ignoring case: the names the
+/// command line takes, for enums that can't rely on clap to parse them.
+pub(crate) fn parse_name<T: Copy>(s: &str, what: &str, names: &[(&str, T)]) -> Result<T, String> {
+    names
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s.trim()))
+        .map(|&(_, value)| value)
+        .ok_or_else(|| format!("unknown {} '{}'", what, s))
+}
+
+/// `msb` or `lsb`.
+impl FromStr for BitOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_name(s, "bit order", &[("msb", BitOrder::Msb), ("lsb", BitOrder::Lsb)])
+    }
+}
+
+/// `big` (`be`), `little` (`le`), `pdp` (`middle`), `word-swap16`,
+/// `word-swap32` or `word-swap64`.
+impl FromStr for ByteOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_name(
+            s,
+            "byte order",
+            &[
+                ("big", ByteOrder::Big),
+                ("be", ByteOrder::Big),
+                ("little", ByteOrder::Little),
+                ("le", ByteOrder::Little),
+                ("pdp", ByteOrder::Pdp),
+                ("middle", ByteOrder::Pdp),
+                ("word-swap16", ByteOrder::WordSwap16),
+                ("word-swap32", ByteOrder::WordSwap32),
+                ("word-swap64", ByteOrder::WordSwap64),
+            ],
+        )
+    }
+}
+
+impl BitOrder {
+    /// The byte order a read uses when none is given: big-endian for `Msb`,
+    /// little-endian for `Lsb`.
+    pub fn natural_byte_order(self) -> ByteOrder {
+        match self {
+            BitOrder::Msb => ByteOrder::Big,
+            BitOrder::Lsb => ByteOrder::Little,
+        }
+    }
+}
+
+impl ByteOrder {
+    /// Size in bytes of the unit the field length must be a multiple of.
+    pub fn unit_bytes(self) -> usize {
+        match self {
+            ByteOrder::Big | ByteOrder::Little => 1,
+            ByteOrder::Pdp | ByteOrder::WordSwap16 => 2,
+            ByteOrder::WordSwap32 => 4,
+            ByteOrder::WordSwap64 => 8,
+        }
+    }
+}
@@ -1,8 +1,17 @@
-use bitvec::prelude::*;
 use clap::{Parser, ValueEnum};
-use memmap2::MmapOptions;
-use num_bigint::BigUint;
-use std::{fs::File, str::FromStr};
+use itty_bitty::{
+    ArchiveFormat, BitOrder, BitReader, BitStats, BitWriter, ByteChunks, ByteOrder, CHUNK_BYTES, Compression,
+    Expectation, FieldSpec, FloatFormat, FloatParts, Input, JournalEntry, Layout, LayoutError,
+    Length, Offset, Padding, Pattern, Pointer, ReadError, SignedEncoding, append_journal, decode_float,
+    analyze, best_shift, diff_ranges, find_member, from_signed, journal_path, map_writable, parse_field_list, parse_range, parse_signed,
+    parse_value, read_journal, search_range, split_member_path, to_signed, write_journal,
+};
+use memmap2::MmapMut;
+use num_bigint::{BigInt, BigUint, Sign};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::os::unix::io::AsRawFd;
 
 #[repr(C)]
@@ -86,7 +95,7 @@ fn best_fit_width(term_width: u16, offset_width: u16) -> u16 {
         (term_width - offset_width - 5) / 4
     } else {
         8 // fallback minimum
-    } as u16;
+    };
     
     // Find largest valid width <= available
     for width in [64, 48, 32, 24, 16, 12, 8].iter() {
@@ -97,432 +106,1961 @@ fn best_fit_width(term_width: u16, offset_width: u16) -> u16 {
     8 // minimum fallback
 }
 
-#[derive(Debug)]
-enum OffsetError {
-    ParseError(String),
-    InvalidBitOffset,
-}
+#[derive(Parser)]
+#[command(about = "Read an arbitrary-sized bitfield from a file at any bit offset")]
+#[command(arg_required_else_help = true)]
+struct Args {
+    /// Positional arguments: [OFFSET LENGTH FILE] or [FILE OFFSET LENGTH], or
+    /// [RANGE FILE] or [FILE RANGE] with a RANGE such as 0x100..0x200
+    /// Supports both orderings for flexible command-line use. Options must come first.
+    #[arg(trailing_var_arg = true)]
+    positional: Vec<String>,
 
-impl std::fmt::Display for OffsetError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            OffsetError::ParseError(msg) => write!(f, "Invalid offset: {}", msg),
-            OffsetError::InvalidBitOffset => write!(f, "Bit offset must be 0-7"),
-        }
-    }
-}
+    /// Bit order (msb = most significant bit first, lsb = least significant bit first)
+    #[arg(short = 'e', long, value_enum, default_value = "msb")]
+    order: BitOrder,
+
+    /// Byte order, applied to the bytes assembled with the bit order
+    /// [default: big for msb, little for lsb]
+    #[arg(short = 'B', long, value_enum)]
+    byte_order: Option<ByteOrder>,
+
+    /// Decompress FILE first, so offsets apply to the payload. The format is
+    /// detected unless given as --decompress=FORMAT
+    #[arg(short = 'z', long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true)]
+    decompress: Option<Option<Compression>>,
+
+    /// With FILE::MEMBER, count offsets from the start of the archive holding
+    /// the member rather than the member's own data
+    #[arg(long)]
+    container_offsets: bool,
+
+    /// Output format
+    #[arg(short = 'f', long, value_enum, default_value = "hex")]
+    format: OutputFormat,
+
+    /// Print each field as a machine-readable record of its position and
+    /// value in several forms, instead of just the value
+    #[arg(
+        short = 'o',
+        long,
+        value_enum,
+        default_value = "text",
+        conflicts_with_all = ["structure", "search"]
+    )]
+    output: OutputMode,
+
+    /// Show offset info (both from start and from end)
+    #[arg(short = 'v', long)]
+    verbose: bool,
+
+    /// Interpret the field as a signed number (decimal, hex and binary formats)
+    #[arg(short = 's', long, value_enum)]
+    signed: Option<SignedEncoding>,
+
+    /// With a float format, also print sign, exponent, mantissa and class
+    #[arg(short = 'b', long)]
+    breakdown: bool,
 
-impl std::error::Error for OffsetError {}
+    /// Bias K for offset-binary (excess-K) [default: 2^(LENGTH-1)]
+    #[arg(long, requires = "signed", allow_hyphen_values = true, value_parser = parse_bias)]
+    bias: Option<BigInt>,
 
-fn parse_offset(s: &str) -> Result<Offset, String> {
-    Offset::from_str(s).map_err(|e| e.to_string())
+    /// Read several fields: comma-separated OFFSET:LENGTH, or just LENGTH to
+    /// continue where the previous field ended. Only FILE is then positional
+    #[arg(short = 'F', long, value_name = "LIST", allow_hyphen_values = true)]
+    fields: Vec<String>,
+
+    /// Read a field at OFFSET (repeatable, after any --fields)
+    #[arg(
+        short = 'r',
+        long = "read",
+        num_args = 2,
+        value_names = ["OFFSET", "LENGTH"],
+        allow_hyphen_values = true
+    )]
+    reads: Vec<String>,
+
+    /// Read the queries in QUERIES (`-` for stdin), one per line: OFFSET
+    /// LENGTH or a range, then optionally a format, a bit order and a label.
+    /// Blank lines and `#` comments are skipped. Only FILE is then positional
+    #[arg(
+        long,
+        value_name = "QUERIES",
+        conflicts_with_all = ["fields", "reads", "structure", "search", "expect"]
+    )]
+    batch: Option<String>,
+
+    /// Decode the structure described by a TOML definition file. Only FILE
+    /// (optionally followed by the structure's OFFSET) is then positional
+    #[arg(short = 'S', long = "struct", value_name = "DEF")]
+    structure: Option<PathBuf>,
+
+    /// Search FILE for a hex (0x..), binary (0b..) or decimal PATTERN at
+    /// every bit offset and print where it occurs. Hex and binary digits may
+    /// be `x` or `?` wildcards, and PATTERN/MASK only compares the mask's set
+    /// bits. FILE may be followed by an OFFSET and LENGTH, or a range, to
+    /// search within
+    #[arg(long, value_name = "PATTERN", conflicts_with = "structure")]
+    search: Option<String>,
+
+    /// Check each field against an expected VALUE, in order (repeatable): a
+    /// number, "text", VALUE/MASK or a range such as 1..=7. Exits with
+    /// status 1 if any field doesn't match
+    #[arg(
+        long,
+        value_name = "VALUE",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["structure", "search"]
+    )]
+    expect: Vec<String>,
+
+    /// With several FILEs, group them by the field's value and count each
+    /// group, instead of listing each file
+    #[arg(short = 'G', long, conflicts_with_all = ["output", "expect"])]
+    group: bool,
+
+    /// With --expect, only compare the bits set in MASK
+    #[arg(long, value_name = "MASK", requires = "expect")]
+    expect_mask: Option<String>,
+
+    /// Patch the field at OFFSET and LENGTH in FILE to VALUE: a number in
+    /// decimal, hex (0x) or binary (0b), negative with --signed (two's
+    /// complement without), or "text". Bits outside the field are untouched,
+    /// and each patch is recorded in FILE.itty-bitty-undo for --undo
+    #[arg(
+        short = 'w',
+        long,
+        value_name = "VALUE",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["structure", "search", "batch", "fields", "reads", "expect", "output"]
+    )]
+    write: Option<String>,
+
+    /// Revert the last --write to FILE, from its undo journal. Only FILE is
+    /// then positional
+    #[arg(
+        long,
+        conflicts_with_all = ["write", "structure", "search", "batch", "fields", "reads", "expect", "output"]
+    )]
+    undo: bool,
+
+    /// With --write or --undo, show the field before and after without
+    /// changing FILE
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// Copy the bits at OFFSET and LENGTH into OUT (`-` for stdout), shifted
+    /// to start on a byte boundary
+    #[arg(
+        long,
+        value_name = "OUT",
+        conflicts_with_all = [
+            "structure", "search", "batch", "fields", "reads", "expect", "output", "write",
+            "undo", "byte_order"
+        ]
+    )]
+    carve: Option<PathBuf>,
+
+    /// With --carve, how to fill the rest of a final partial byte
+    #[arg(long, value_enum, requires = "carve", default_value = "zero")]
+    pad: Padding,
+
+    /// Compare FILE (or OFFSET and LENGTH, or a range, of it) with OTHER bit
+    /// by bit and list the ranges that differ; with -f hex-ascii, show them
+    /// as a hexdump. OTHER can be FILE itself with --diff-offset. Exits with
+    /// status 1 if anything differs
+    #[arg(
+        long,
+        value_name = "OTHER",
+        conflicts_with_all = [
+            "structure", "search", "batch", "fields", "reads", "expect", "output", "write",
+            "undo", "carve", "byte_order"
+        ]
+    )]
+    diff: Option<String>,
+
+    /// With --diff, where in OTHER to start [default: FILE's offset]
+    #[arg(long, value_name = "OFFSET", requires = "diff", allow_hyphen_values = true)]
+    diff_offset: Option<String>,
+
+    /// With --diff, also try OTHER shifted by up to BITS bits either way and
+    /// compare where it lines up best, for content re-encoded at a new offset
+    #[arg(long, value_name = "BITS", requires = "diff")]
+    diff_shift: Option<usize>,
+
+    /// Report entropy, popcount, the bias at each bit of a byte, runs and a
+    /// byte histogram for FILE (or OFFSET and LENGTH, or a range, of it)
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "structure", "search", "batch", "fields", "reads", "expect", "output", "write",
+            "undo", "carve", "diff", "byte_order"
+        ]
+    )]
+    analyze: bool,
+
+    /// With --analyze, report on each window of LENGTH bits in turn
+    #[arg(long, value_name = "LENGTH", requires = "analyze")]
+    window: Option<String>,
+
+    /// With --window, how far each window starts from the last [default:
+    /// the window's length]
+    #[arg(long, value_name = "LENGTH", requires = "window")]
+    step: Option<String>,
+
+    /// With --search, only report offsets that are a multiple of BITS
+    #[arg(short = 'a', long, value_name = "BITS", requires = "search", default_value_t = 1)]
+    align: usize,
 }
 
-fn parse_length(s: &str) -> Result<Length, String> {
-    Length::from_str(s).map_err(|e| e.to_string())
+fn parse_bias(s: &str) -> Result<BigInt, String> {
+    parse_signed(s)
+        .map(|(bias, _)| bias)
+        .ok_or_else(|| format!("Invalid bias: {}", s))
 }
 
-#[derive(Debug, Clone)]
-struct Length {
-    bits: u64,
+/// A field from OFFSET and LENGTH arguments.
+fn parse_field(offset: &str, length: &str) -> Result<FieldSpec, Box<dyn std::error::Error>> {
+    let offset = Offset::from_str(offset).map_err(|e| format!("Invalid offset: {}", e))?;
+    let length = Length::from_str(length).map_err(|e| format!("Invalid length: {}", e))?;
+    Ok(FieldSpec::new(offset, length))
 }
 
-impl Length {
-    fn to_bits(&self) -> u64 {
-        self.bits
+/// The field at the front of `args`, a RANGE or an OFFSET and LENGTH, and
+/// the arguments after it.
+fn leading_field<'a, 'b>(
+    args: &'b [&'a str],
+) -> Result<(FieldSpec, &'b [&'a str]), Box<dyn std::error::Error>> {
+    let Some(first) = args.first() else {
+        return Err("Expected OFFSET and LENGTH, or a range".into());
+    };
+    match parse_range(first) {
+        Ok(field) => Ok((field, &args[1..])),
+        Err(e) if args.len() < 2 => Err(format!("Invalid range: {}", e).into()),
+        Err(_) => Ok((parse_field(args[0], args[1])?, &args[2..])),
     }
 }
 
-impl FromStr for Length {
-    type Err = OffsetError;
-    
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.replace(&[',', '_', '\''][..], "");
-        
-        // Try to split into bytes:bits
-        let (num_str, bits_part) = if let Some((a, b)) = s.split_once(|c| c == ':' || c == '.') {
-            let bits = b.parse::<u64>()
-                .map_err(|_| OffsetError::ParseError("Invalid bit count".into()))?;
-            if bits > 7 {
-                return Err(OffsetError::InvalidBitOffset);
+impl Args {
+    fn positional_args(&self) -> Vec<&str> {
+        // A `--` guarding negative offsets lands here once the first
+        // positional has switched clap into trailing mode
+        self.positional
+            .iter()
+            .map(String::as_str)
+            .filter(|a| *a != "--")
+            .collect()
+    }
+
+    /// The file and the fields to read from it, from either the positional
+    /// OFFSET/LENGTH or --fields/--read.
+    fn parse_positional(&self) -> Result<(String, Vec<FieldSpec>), Box<dyn std::error::Error>> {
+        let positional = self.positional_args();
+
+        if !self.fields.is_empty() || !self.reads.is_empty() {
+            let [file] = positional[..] else {
+                return Err("Expected only FILE when using --fields or --read".into());
+            };
+            let mut fields = Vec::new();
+            for list in &self.fields {
+                fields.extend(
+                    parse_field_list(list).map_err(|e| format!("Invalid field list: {}", e))?,
+                );
+            }
+            for pair in self.reads.chunks(2) {
+                fields.push(parse_field(&pair[0], &pair[1])?);
+            }
+            return Ok((file.to_string(), fields));
+        }
+
+        // FIELD FILE or FILE FIELD, where FIELD is a RANGE or an OFFSET and
+        // LENGTH. Paths like `../x` can hold a `..` too, so the field is
+        // whichever end parses
+        let whole_field = |args: &[&str]| -> Result<FieldSpec, Box<dyn std::error::Error>> {
+            match leading_field(args)? {
+                (field, []) => Ok(field),
+                _ => Err("Expected FILE, OFFSET, and LENGTH arguments".into()),
             }
-            (a, Some(bits))
-        } else {
-            (s.as_str(), None)
         };
-        
-        // Parse the number part
-        let num = if num_str.starts_with("0x") || num_str.starts_with("0X") {
-            u64::from_str_radix(&num_str[2..], 16)
-        } else if num_str.starts_with('$') {
-            u64::from_str_radix(&num_str[1..], 16)
-        } else if num_str.ends_with('h') || num_str.ends_with('H') {
-            u64::from_str_radix(&num_str[..num_str.len()-1], 16)
-        } else {
-            num_str.parse::<u64>()
-        }.map_err(|e| OffsetError::ParseError(e.to_string()))?;
-        
-        // If bits specified, num is bytes; otherwise num is total bits
-        let total_bits = if let Some(bit_offset) = bits_part {
-            (num * 8) + bit_offset
-        } else {
-            num
+        match positional[..] {
+            [] | [_] => Err("Expected FILE, OFFSET, and LENGTH arguments".into()),
+            [first, ref rest @ ..] => match whole_field(&positional[..positional.len() - 1]) {
+                Ok(field) => Ok((positional[positional.len() - 1].to_string(), vec![field])),
+                Err(_) => Ok((first.to_string(), vec![whole_field(rest)?])),
+            },
+        }
+    }
+
+    /// The field and files for `OFFSET LENGTH FILE...` or `RANGE FILE...`
+    /// with several files or a glob pattern, which each expand to the files
+    /// they match. `None` for a single file.
+    fn parse_files(&self) -> Result<Option<FieldFiles>, Box<dyn std::error::Error>> {
+        if !self.fields.is_empty() || !self.reads.is_empty() || self.batch.is_some() {
+            return Ok(None);
+        }
+        let positional = self.positional_args();
+        let Ok((field, names)) = leading_field(&positional) else {
+            return Ok(None);
         };
-        
-        Ok(Length {
-            bits: total_bits,
-        })
+        // A path that exists is taken as it is, even if it looks like a glob
+        let is_glob =
+            |name: &&str| name.contains(['*', '?', '[']) && !Path::new(name).exists();
+        if names.is_empty() || names.len() == 1 && !names.iter().any(is_glob) {
+            return Ok(None);
+        }
+        let mut files = Vec::new();
+        for name in names {
+            if !is_glob(name) {
+                files.push(name.to_string());
+                continue;
+            }
+            let matches = glob::glob(name).map_err(|e| format!("Invalid glob '{}': {}", name, e))?;
+            let before = files.len();
+            for path in matches {
+                let path = path.map_err(|e| e.to_string())?;
+                files.push(path.to_string_lossy().into_owned());
+            }
+            if files.len() == before {
+                return Err(format!("No files match '{}'", name).into());
+            }
+        }
+        Ok(Some((field, files)))
+    }
+
+    /// The --expect values, one for each of `fields` fields, or none.
+    fn expectations(&self, fields: usize) -> Result<Vec<Expectation>, Box<dyn std::error::Error>> {
+        if !self.expect.is_empty() && self.expect.len() != fields {
+            return Err(format!(
+                "{} --expect values for {} fields; give one per field",
+                self.expect.len(),
+                fields
+            )
+            .into());
+        }
+        self.expect
+            .iter()
+            .map(|value| {
+                let expectation = Expectation::from_str(value)
+                    .map_err(|e| format!("Invalid --expect: {}", e))?;
+                Ok(match &self.expect_mask {
+                    Some(mask) => expectation
+                        .with_mask(mask)
+                        .map_err(|e| format!("Invalid --expect-mask: {}", e))?,
+                    None => expectation,
+                })
+            })
+            .collect()
     }
 }
 
-#[derive(Debug, Clone)]
-struct Offset {
-    bytes: u64,
-    bits: u32,  // 0-7
-    is_negative: bool,
+#[derive(Clone, ValueEnum)]
+enum OutputFormat {
+    Decimal,
+    Hex,
+    Binary,
+    Ascii,
+    HexAscii,
+    /// IEEE 754 half precision float
+    #[value(alias = "f16")]
+    Binary16,
+    /// bfloat16 float
+    #[value(alias = "bf16")]
+    Bfloat16,
+    /// IEEE 754 single precision float
+    #[value(alias = "f32")]
+    Binary32,
+    /// IEEE 754 double precision float
+    #[value(alias = "f64")]
+    Binary64,
+    /// IEEE 754 quadruple precision float
+    #[value(alias = "f128")]
+    Binary128,
+    /// x87 80-bit extended precision float
+    #[value(alias = "f80")]
+    X87,
 }
 
-impl Offset {
-    fn to_bits(&self) -> i64 {
-        let total_bits = (self.bytes * 8) as i64 + self.bits as i64;
-        if self.is_negative { -total_bits } else { total_bits }
+impl OutputFormat {
+    fn float_format(&self) -> Option<FloatFormat> {
+        match self {
+            OutputFormat::Binary16 => Some(FloatFormat::Binary16),
+            OutputFormat::Bfloat16 => Some(FloatFormat::Bfloat16),
+            OutputFormat::Binary32 => Some(FloatFormat::Binary32),
+            OutputFormat::Binary64 => Some(FloatFormat::Binary64),
+            OutputFormat::Binary128 => Some(FloatFormat::Binary128),
+            OutputFormat::X87 => Some(FloatFormat::X87Extended),
+            _ => None,
+        }
     }
 }
 
-impl FromStr for Offset {
-    type Err = OffsetError;
-    
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (sign, s) = match s.strip_prefix('-') {
-            Some(rest) => (true, rest.trim_start()),
-            None => (false, s.trim_start()),
-        };
-        
-        // Remove thousands separators
-        let s = s.replace(&[',', '_', '\''][..], "");
-        
-        // Try to split into bytes:bits (or bytes.bits)
-        let (num_str, bits_part) = if let Some((a, b)) = s.split_once(|c| c == ':' || c == '.') {
-            let bits = b.parse::<u32>()
-                .map_err(|_| OffsetError::ParseError("Invalid bit count".into()))?;
-            if bits > 7 {
-                return Err(OffsetError::InvalidBitOffset);
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq)]
+enum OutputMode {
+    /// Just the value, in --format
+    Text,
+    /// A JSON array of records
+    Json,
+    /// One JSON record per line
+    Ndjson,
+    /// A header row, then one row per record
+    Csv,
+}
+
+/// How to extract and print each field.
+#[derive(Clone)]
+struct Output {
+    order: BitOrder,
+    byte_order: Option<ByteOrder>,
+    format: OutputFormat,
+    signed: Option<SignedEncoding>,
+    bias: Option<BigInt>,
+    breakdown: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    // Before any mode runs, so nothing is written that -n was meant to stop
+    if args.dry_run && args.write.is_none() && !args.undo {
+        return Err("--dry-run needs --write or --undo".into());
+    }
+    if let Some(def) = &args.structure {
+        return dump_struct(&args, def);
+    }
+    if let Some(pattern) = &args.search {
+        return run_search(&args, pattern);
+    }
+    if let Some(value) = &args.write {
+        return run_write(&args, value);
+    }
+    if args.undo {
+        return run_undo(&args);
+    }
+    if let Some(out) = &args.carve {
+        return run_carve(&args, out);
+    }
+    if let Some(other) = &args.diff {
+        return run_diff(&args, other);
+    }
+    if args.analyze {
+        return run_analyze(&args);
+    }
+    if let Some((field, files)) = args.parse_files()? {
+        return run_many(&args, &field, &files);
+    }
+    if args.group {
+        return Err("--group needs several FILEs or a glob".into());
+    }
+    let verbose = args.verbose;
+    let (file, queries, expectations, labelled, limit) = match &args.batch {
+        Some(batch) => {
+            let [file] = args.positional_args()[..] else {
+                return Err("Expected only FILE when using --batch".into());
+            };
+            if batch == "-" && file == "-" {
+                return Err("Can't read both --batch queries and FILE from stdin".into());
             }
-            (a, Some(bits))
-        } else {
-            (s.as_str(), None)
-        };
-        
-        // Parse the number part
-        let num = if num_str.starts_with("0x") || num_str.starts_with("0X") {
-            u64::from_str_radix(&num_str[2..], 16)
-        } else if num_str.starts_with('$') {
-            u64::from_str_radix(&num_str[1..], 16)
-        } else if num_str.ends_with('h') || num_str.ends_with('H') {
-            u64::from_str_radix(&num_str[..num_str.len()-1], 16)
-        } else {
-            num_str.parse::<u64>()
-        }.map_err(|e| OffsetError::ParseError(e.to_string()))?;
-        
-        // If bits were specified via colon/dot, treat num as bytes
-        // Otherwise, treat num as total bits
-        let (bytes, bits) = if let Some(bit_offset) = bits_part {
-            (num, bit_offset)
-        } else {
-            (num / 8, (num % 8) as u32)
+            // Every result is labelled, so they can be told apart
+            (file.to_string(), read_queries(batch)?, Vec::new(), true, None)
+        }
+        None => {
+            let (file, fields) = args.parse_positional()?;
+            let expectations = args.expectations(fields.len())?;
+            // A single field prints just its value; several are labelled
+            let labelled = fields.len() > 1;
+            let limit = bytes_needed(&fields);
+            let queries: Box<dyn Iterator<Item = QueryResult>> =
+                Box::new(fields.into_iter().map(|field| Ok(Query::new(field))));
+            (file, queries, expectations, labelled, limit)
+        }
+    };
+    let input = open_input_up_to(&args, &file, limit)?;
+    let base_output = Output {
+        order: args.order,
+        byte_order: args.byte_order,
+        format: args.format,
+        signed: args.signed,
+        bias: args.bias,
+        breakdown: args.breakdown,
+    };
+
+    let reader = BitReader::new(&input);
+    let mut records = RecordWriter::new(args.output);
+    let mut mismatches = Vec::new();
+
+    let file_bits = reader.len_bits();
+
+    if verbose {
+        eprintln!(
+            "File: {} bytes ({} bits)",
+            input.len(),
+            file_bits
+        );
+    }
+
+    // Fields without an offset continue from the end of the previous one
+    let mut cursor = 0;
+    for (i, query) in queries.enumerate() {
+        let query = query?;
+        let field = &query.field;
+        let in_context = |e: ReadError| match &query.source {
+            Some(source) => format!("{}: {}", source, e),
+            None => e.to_string(),
         };
-        
-        Ok(Offset {
-            bytes,
-            bits,
-            is_negative: sign,
-        })
+        let range = reader.resolve_field(field, cursor).map_err(in_context)?;
+        cursor = range.end;
+        let output = query.output(&base_output);
+
+        if let Some(expectation) = expectations.get(i) {
+            let value = read_number(&reader, range.clone(), &output)?;
+            if !expectation.matches(&value) {
+                let masked = expectation.masked(&value);
+                mismatches.push(format!(
+                    "{:#x}:{} [{} bits]: expected {}, got {}{}",
+                    range.start / 8,
+                    range.start % 8,
+                    range.len(),
+                    expectation,
+                    expectation.format_value(&masked),
+                    if masked == value {
+                        String::new()
+                    } else {
+                        format!(" (unmasked {})", expectation.format_value(&value))
+                    }
+                ));
+            }
+        }
+
+        if verbose {
+            let pointers = reader
+                .pointers(field.offset.as_ref(), Some(&field.length))
+                .map_err(in_context)?;
+            print_pointers(&pointers);
+            let offset_bits = range.start;
+            let from_end = file_bits - offset_bits;
+            let offset = match &field.offset {
+                Some(offset) if !offset.needs_data() => offset.clone(),
+                _ => Offset::from_bits(offset_bits as i64),
+            };
+            eprintln!(
+                "Reading {} bits at offset {} ({:#x}) = ({} bytes, {} bits) = ({:#x}:{} bits) from end = -{}",
+                range.len(),
+                offset_bits,
+                offset_bits,
+                offset.bytes,
+                offset.bits,
+                offset.bytes,
+                offset.bits,
+                from_end
+            );
+        }
+
+        // Labelled with the query's own label, or the offset in the same
+        // bytes:bits form OFFSET accepts
+        let label = query.label.clone().or_else(|| {
+            labelled.then(|| format!("{:#x}:{}", range.start / 8, range.start % 8))
+        });
+        if args.output != OutputMode::Text {
+            let mut record = field_record(&reader, &file, range, &output)?;
+            record.label = args.batch.is_some().then_some(label).flatten();
+            records.write(record)?;
+            continue;
+        }
+        print_field(&reader, range, label.as_deref(), &output)?;
     }
-}
+    records.finish()?;
 
-#[derive(Clone, ValueEnum, Debug)]
-enum BitOrder {
-    Msb,
-    Lsb,
+    if !mismatches.is_empty() {
+        for mismatch in &mismatches {
+            eprintln!("{}", mismatch);
+        }
+        std::process::exit(1);
+    }
+    Ok(())
 }
 
-#[derive(Parser)]
-#[command(about = "Read an arbitrary-sized bitfield from a file at any bit offset")]
-#[command(arg_required_else_help = true)]
-struct Args {
-    /// Positional arguments: [OFFSET LENGTH FILE] or [FILE OFFSET LENGTH]
-    /// Supports both orderings for flexible command-line use. Options must come first.
-    #[arg(trailing_var_arg = true)]
-    positional: Vec<String>,
+/// A field and the files to read it from.
+type FieldFiles = (FieldSpec, Vec<String>);
 
-    /// Bit order (msb = most significant bit first, lsb = least significant bit first)
-    #[arg(short = 'e', long, value_enum, default_value = "msb")]
-    order: BitOrder,
+type QueryResult = Result<Query, Box<dyn std::error::Error>>;
 
-    /// Output format
-    #[arg(short = 'f', long, value_enum, default_value = "hex")]
-    format: OutputFormat,
+/// A field to read, with the format, bit order and label its --batch line
+/// gave, if any.
+struct Query {
+    field: FieldSpec,
+    format: Option<OutputFormat>,
+    order: Option<BitOrder>,
+    label: Option<String>,
+    /// `QUERIES:LINE`, to say which query an error came from
+    source: Option<String>,
+}
 
-    /// Show offset info (both from start and from end)
-    #[arg(short = 'v', long)]
-    verbose: bool,
+impl Query {
+    fn new(field: FieldSpec) -> Self {
+        Query {
+            field,
+            format: None,
+            order: None,
+            label: None,
+            source: None,
+        }
+    }
+
+    /// Parse a --batch line: `OFFSET LENGTH [format] [order] [label]`, with
+    /// a range in place of OFFSET and LENGTH if wanted. The label is the
+    /// rest of the line.
+    fn parse(line: &str) -> QueryResult {
+        let tokens = split_words(line);
+        let (field, mut rest) = leading_field(&tokens)?;
+        let mut query = Query::new(field);
+        if let Some((first, tail)) = rest.split_first()
+            && let Ok(format) = OutputFormat::from_str(first, true)
+        {
+            query.format = Some(format);
+            rest = tail;
+        }
+        if let Some((first, tail)) = rest.split_first()
+            && let Ok(order) = first.parse::<BitOrder>()
+        {
+            query.order = Some(order);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            query.label = Some(rest.join(" "));
+        }
+        Ok(query)
+    }
+
+    /// `base` with this query's format and bit order.
+    fn output(&self, base: &Output) -> Output {
+        let mut output = base.clone();
+        if let Some(format) = &self.format {
+            output.format = format.clone();
+        }
+        if let Some(order) = self.order {
+            output.order = order;
+        }
+        output
+    }
 }
 
-impl Args {
-    fn parse_positional(self) -> Result<(String, Offset, Length), Box<dyn std::error::Error>> {
-        if self.positional.len() < 3 {
-            return Err("Expected FILE, OFFSET, and LENGTH arguments".into());
-        }
-
-        // Try to detect order by checking if first arg is a valid file
-        // Then try offset/length parsing
-        let file: String;
-        let offset: Offset;
-        let length: Length;
-
-        // Strategy: check if first arg could be offset/length
-        let first_is_offset = {
-            if let Ok(_) = Offset::from_str(&self.positional[0]) {
-                if let Ok(_len) = Length::from_str(&self.positional[1]) {
-                    true  // First two look like offset/length
-                } else {
-                    false
+/// Split a --batch line at whitespace, except within quotes or brackets, so
+/// `after:"A B"` and `@[ 0x3c : 32 ]` stay whole.
+fn split_words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '[' | '(' if !quoted => depth += 1,
+            ']' | ')' if !quoted => depth -= 1,
+            c if c.is_whitespace() && !quoted && depth <= 0 => {
+                if let Some(begin) = start.take() {
+                    words.push(&line[begin..i]);
                 }
-            } else {
-                false
+                continue;
             }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(begin) = start {
+        words.push(&line[begin..]);
+    }
+    words
+}
+
+/// The queries in a --batch file, or on stdin for `-`, parsed a line at a
+/// time as they're read so a stream of queries is answered as it arrives.
+fn read_queries(path: &str) -> Result<Box<dyn Iterator<Item = QueryResult>>, Box<dyn std::error::Error>> {
+    let (name, lines): (String, Box<dyn BufRead>) = if path == "-" {
+        ("<stdin>".to_string(), Box::new(std::io::stdin().lock()))
+    } else {
+        let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        (path.to_string(), Box::new(BufReader::new(file)))
+    };
+    Ok(Box::new(lines.lines().enumerate().filter_map(move |(i, line)| {
+        let source = format!("{}:{}", name, i + 1);
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Some(Err(format!("{}: {}", source, e).into())),
         };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        Some(match Query::parse(line) {
+            Ok(query) => Ok(Query {
+                source: Some(source),
+                ..query
+            }),
+            Err(e) => Err(format!("{}: {}", source, e).into()),
+        })
+    })))
+}
 
-        if first_is_offset {
-            // OFFSET LENGTH FILE order
-            if self.positional.len() < 3 {
-                return Err("Expected OFFSET LENGTH FILE or FILE OFFSET LENGTH".into());
+/// Read one field from each of `files`: a value per file, labelled with its
+/// name, or the files grouped by value with --group. A file that can't be
+/// read is reported and skipped, and makes the exit status 1.
+fn run_many(args: &Args, field: &FieldSpec, files: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let expectations = args.expectations(1)?;
+    let output = Output {
+        order: args.order,
+        byte_order: args.byte_order,
+        format: args.format.clone(),
+        signed: args.signed,
+        bias: args.bias.clone(),
+        breakdown: args.breakdown,
+    };
+    let mut records = RecordWriter::new(args.output);
+    let mut failed = false;
+    // Values in the order first seen, with the files that have each
+    let mut groups: Vec<(String, Vec<&str>)> = Vec::new();
+
+    let limit = bytes_needed(std::slice::from_ref(field));
+    for file in files {
+        let result = (|| -> Result<(), Box<dyn std::error::Error>> {
+            let input = open_input_up_to(args, file, limit)?;
+            let reader = BitReader::new(&input);
+            let range = reader
+                .resolve_field(field, 0)
+                .map_err(|e| e.to_string())?;
+            if let Some(expectation) = expectations.first() {
+                let value = read_number(&reader, range.clone(), &output)?;
+                if !expectation.matches(&value) {
+                    return Err(format!(
+                        "expected {}, got {}",
+                        expectation,
+                        expectation.format_value(&expectation.masked(&value))
+                    )
+                    .into());
+                }
             }
-            offset = Offset::from_str(&self.positional[0])
-                .map_err(|e| format!("Invalid offset: {}", e))?;
-            length = Length::from_str(&self.positional[1])
-                .map_err(|e| format!("Invalid length: {}", e))?;
-            file = self.positional[2].clone();
-        } else {
-            // FILE OFFSET LENGTH order
-            if self.positional.len() < 3 {
-                return Err("Expected FILE OFFSET LENGTH or OFFSET LENGTH FILE".into());
+            if args.group {
+                let value = format_value(&reader, range, &output)?;
+                match groups.iter_mut().find(|(v, _)| *v == value) {
+                    Some((_, members)) => members.push(file),
+                    None => groups.push((value, vec![file])),
+                }
+            } else if args.output != OutputMode::Text {
+                records.write(field_record(&reader, file, range, &output)?)?;
+            } else {
+                print_field(&reader, range, Some(file), &output)?;
             }
-            file = self.positional[0].clone();
-            offset = Offset::from_str(&self.positional[1])
-                .map_err(|e| format!("Invalid offset: {}", e))?;
-            length = Length::from_str(&self.positional[2])
-                .map_err(|e| format!("Invalid length: {}", e))?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            eprintln!("{}: {}", file, e);
+            failed = true;
         }
+    }
+    records.finish()?;
 
-        Ok((file, offset, length))
+    // Most common value first
+    groups.sort_by_key(|(_, members)| std::cmp::Reverse(members.len()));
+    for (value, members) in &groups {
+        println!(
+            "{}\t{} file{}",
+            value,
+            members.len(),
+            if members.len() == 1 { "" } else { "s" }
+        );
+        for file in members {
+            println!("\t{}", file);
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
     }
+    Ok(())
 }
 
-#[derive(Clone, ValueEnum)]
-enum OutputFormat {
-    Decimal,
-    Hex,
-    Binary,
-    Ascii,
-    HexAscii,
+/// A field's value in --format as one line, for --group.
+fn format_value(
+    reader: &BitReader,
+    range: std::ops::Range<usize>,
+    output: &Output,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if output.breakdown || matches!(output.format, OutputFormat::HexAscii) {
+        return Err("--group needs a one-line --format".into());
+    }
+    if let OutputFormat::Ascii = output.format {
+        let byte_order = output
+            .byte_order
+            .unwrap_or_else(|| output.order.natural_byte_order());
+        let bytes: Vec<u8> = reader
+            .byte_chunks(range, output.order, byte_order, CHUNK_BYTES)
+            .map_err(|e| e.to_string())?
+            .flatten()
+            .collect();
+        return Ok(bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect());
+    }
+    if let Some(float_format) = output.format.float_format() {
+        let bits = range.len();
+        if bits != float_format.width() {
+            return Err(ReadError::WrongWidth {
+                bits,
+                expected: float_format.width(),
+            }
+            .to_string()
+            .into());
+        }
+        let value = match output.byte_order {
+            Some(byte_order) => reader.read_with_byte_order(range, output.order, byte_order),
+            None => reader.read(range, output.order),
+        }
+        .map_err(|e| e.to_string())?;
+        return Ok(decode_float(&value, float_format).to_string());
+    }
+    Ok(format_number(&read_number(reader, range, output)?, &output.format))
 }
 
-fn extract_bits_to_biguint(bits: &BitSlice<u8, Msb0>) -> BigUint {
-    let n = bits.len();
-    if n == 0 {
-        return BigUint::ZERO;
+/// Show each pointer an offset or length followed, innermost first.
+fn print_pointers(pointers: &[Pointer]) {
+    for pointer in pointers {
+        eprintln!(
+            "Pointer at {:#x}:{} ({} bits, {}) -> byte {:#x}",
+            pointer.at / 8,
+            pointer.at % 8,
+            pointer.bits,
+            pointer
+                .byte_order
+                .to_possible_value()
+                .map_or(String::new(), |v| v.get_name().to_string()),
+            pointer.value
+        );
     }
+}
+
+/// How many bytes of FILE `fields` read, if that's known before reading
+/// any, so that a pipe or device need only be read that far.
+fn bytes_needed(fields: &[FieldSpec]) -> Option<usize> {
+    let mut cursor = 0;
+    let mut end = 0;
+    for field in fields {
+        cursor = field.fixed_end(cursor)?;
+        end = end.max(cursor);
+    }
+    Some(end.div_ceil(8))
+}
 
-    let num_bytes = (n + 7) / 8;
-    let mut bytes = vec![0u8; num_bytes];
-    let padding = num_bytes * 8 - n;
+/// Open FILE (or stdin for `-`), find any `::MEMBER` within it and
+/// decompress the result if asked, naming the file in any error.
+fn open_input(args: &Args, file: &str) -> Result<Input, Box<dyn std::error::Error>> {
+    open_input_up_to(args, file, None)
+}
+
+/// Open FILE as [`open_input`] does, reading at most `limit` bytes of a pipe
+/// or device. Archives and compressed data are always read in full.
+fn open_input_up_to(
+    args: &Args,
+    file: &str,
+    limit: Option<usize>,
+) -> Result<Input, Box<dyn std::error::Error>> {
+    let (path, members) = split_member_path(file);
+    if args.container_offsets && members.is_empty() {
+        return Err("--container-offsets needs a FILE::MEMBER".into());
+    }
+    let fail = |e: std::io::Error| format!("{}: {}", file, e);
+    let limit = limit.filter(|_| members.is_empty() && args.decompress.is_none());
+    let mut input = Input::open_up_to(path, limit).map_err(fail)?;
 
-    for (i, bit) in bits.iter().enumerate() {
-        if *bit {
-            let abs_pos = padding + i;
-            let byte_idx = abs_pos / 8;
-            let bit_idx = 7 - (abs_pos % 8);
-            bytes[byte_idx] |= 1 << bit_idx;
+    let mut container = path.to_string();
+    for (i, name) in members.iter().enumerate() {
+        // A compressed tarball is opened as the tar inside
+        if ArchiveFormat::detect(&input).is_none()
+            && let Some(format) = Compression::detect(&input)
+        {
+            decompress_input(args, &mut input, Some(format)).map_err(fail)?;
+        }
+        let member = find_member(&input, name).map_err(fail)?;
+        if args.verbose {
+            eprintln!(
+                "Member {}: {} bytes at {:#x} in {}{}",
+                name,
+                member.size,
+                member.data_offset,
+                container,
+                if member.compressed {
+                    format!(" ({} bytes compressed)", member.stored_size)
+                } else {
+                    String::new()
+                }
+            );
         }
+        // Offsets relative to the container: stop at the innermost archive
+        if args.container_offsets && i == members.len() - 1 {
+            break;
+        }
+        input = input.into_member(&member).map_err(fail)?;
+        container = name.to_string();
+    }
+
+    if let Some(format) = args.decompress {
+        decompress_input(args, &mut input, format).map_err(fail)?;
     }
+    Ok(input)
+}
 
-    BigUint::from_bytes_be(&bytes)
+fn decompress_input(
+    args: &Args,
+    input: &mut Input,
+    format: Option<Compression>,
+) -> std::io::Result<()> {
+    let compressed = input.len();
+    let format = input.decompress(format)?;
+    if args.verbose {
+        eprintln!(
+            "Decompressed {}: {} bytes -> {} bytes",
+            format.name(),
+            compressed,
+            input.len()
+        );
+    }
+    Ok(())
 }
 
-fn extract_bits_to_biguint_lsb(bits: &BitSlice<u8, Lsb0>) -> BigUint {
-    let mut result = BigUint::ZERO;
-    for (i, bit) in bits.iter().enumerate() {
-        if *bit {
-            result |= BigUint::from(1u8) << i;
+fn dump_struct(args: &Args, def: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let layout: Layout = std::fs::read_to_string(def)
+        .map_err(|e| format!("{}: {}", def.display(), e))?
+        .parse()
+        .map_err(|e: LayoutError| e.to_string())?;
+
+    let (file, offset) = match args.positional_args()[..] {
+        [file] => (file, None),
+        [file, offset] => (
+            file,
+            Some(Offset::from_str(offset).map_err(|e| format!("Invalid offset: {}", e))?),
+        ),
+        _ => return Err("Expected FILE [OFFSET] with --struct".into()),
+    };
+
+    let input = open_input(args, file)?;
+    let reader = BitReader::new(&input);
+    let base = match &offset {
+        Some(offset) => reader.resolve_offset(offset).map_err(|e| e.to_string())?,
+        None => 0,
+    };
+
+    if args.verbose {
+        eprintln!(
+            "File: {} bytes ({} bits)",
+            input.len(),
+            reader.len_bits()
+        );
+        if let Some(offset) = &offset {
+            print_pointers(&reader.pointers(Some(offset), None).map_err(|e| e.to_string())?);
         }
+        eprintln!(
+            "Decoding {} at offset {} ({:#x}:{})",
+            layout.name.as_deref().unwrap_or("structure"),
+            base,
+            base / 8,
+            base % 8
+        );
     }
-    result
+
+    let fields = layout.decode(&reader, base).map_err(|e| e.to_string())?;
+
+    // name  bytes:bits  [bits]  value
+    let offsets: Vec<String> = fields
+        .iter()
+        .map(|f| format!("{:#x}:{}", f.range.start / 8, f.range.start % 8))
+        .collect();
+    let name_width = fields.iter().map(|f| f.name.len()).max().unwrap_or(0);
+    let offset_width = offsets.iter().map(String::len).max().unwrap_or(0);
+    let bits_width = fields
+        .iter()
+        .map(|f| f.range.len().to_string().len() + 2)
+        .max()
+        .unwrap_or(0);
+    for (field, offset) in fields.iter().zip(offsets) {
+        println!(
+            "{:name_width$}  {:>offset_width$}  {:>bits_width$}  {}",
+            field.name,
+            offset,
+            format!("[{}]", field.range.len()),
+            field
+        );
+    }
+
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let order = args.order.clone();
-    let format = args.format.clone();
-    let verbose = args.verbose;
-    let (file, offset, length) = args.parse_positional()?;
+fn run_search(args: &Args, pattern: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let pattern = Pattern::from_str(pattern).map_err(|e| e.to_string())?;
+    let (file, window) = match args.positional_args()[..] {
+        [file] => (file.to_string(), Vec::new()),
+        [_, _] | [_, _, _] => args.parse_positional()?,
+        _ => {
+            return Err(
+                "Expected FILE, optionally with OFFSET and LENGTH or a range, with --search".into(),
+            );
+        }
+    };
+    if args.align == 0 {
+        return Err("Alignment must be at least 1 bit".into());
+    }
+
+    let input = open_input(args, &file)?;
+    let reader = BitReader::new(&input);
+    let range = match reader.resolve_fields(&window).map_err(|e| e.to_string())?.pop() {
+        Some(range) => range,
+        None => 0..reader.len_bits(),
+    };
+
+    if args.verbose {
+        eprintln!(
+            "File: {} bytes ({} bits)",
+            input.len(),
+            reader.len_bits()
+        );
+        eprintln!(
+            "Searching for {} bits {:#x} mask {:#x} every {} bits in bits {}..{}",
+            pattern.bits, pattern.value, pattern.mask, args.align, range.start, range.end
+        );
+    }
 
-    let bits = length.to_bits();
-    if bits == 0 {
-        return Err("Must read at least 1 bit".into());
+    let found = search_range(&reader, &pattern, args.order, args.align, range);
+    // Same bytes:bits form OFFSET accepts, so results can be read back
+    for offset in &found {
+        println!("{:#x}:{}", offset / 8, offset % 8);
+    }
+    if args.verbose {
+        eprintln!("Found {} matches", found.len());
     }
 
-    let file = File::open(&file)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    Ok(())
+}
 
-    let file_bits = mmap.len() * 8;
+/// Open FILE read-only to preview a patch; it has to be a plain file, not
+/// stdin, an archive member or a decompressed payload.
+fn open_for_patch(args: &Args, file: &str) -> Result<Input, Box<dyn std::error::Error>> {
+    let (_, members) = split_member_path(file);
+    if file == "-" || !members.is_empty() || args.decompress.is_some() {
+        return Err("Can only patch a plain FILE, not stdin, a member or decompressed data".into());
+    }
+    open_input(args, file)
+}
 
-    // Calculate total bits from offset
-    let total_bits = offset.to_bits();
-    
-    // Resolve negative offset (relative to end of file)
-    let offset_bits: usize = if total_bits < 0 {
-        let from_end = (-total_bits) as usize;
-        if from_end > file_bits {
-            return Err(format!(
-                "Negative offset -{} exceeds file size ({} bits)",
-                from_end, file_bits
-            )
-            .into());
+/// Map FILE for writing, once a patch is to be made.
+fn map_for_patch(file: &str) -> Result<MmapMut, Box<dyn std::error::Error>> {
+    Ok(map_writable(Path::new(file)).map_err(|e| format!("{}: {}", file, e))?)
+}
+
+/// Show a patch as `bytes:bits [bits]: before -> after`.
+fn print_patch(range: &std::ops::Range<usize>, before: &str, after: &str, dry_run: bool) {
+    println!(
+        "{:#x}:{} [{} bits]: {} -> {}",
+        range.start / 8,
+        range.start % 8,
+        range.len(),
+        before,
+        after
+    );
+    if dry_run {
+        eprintln!("Dry run: nothing written");
+    }
+}
+
+/// The bytes `range` touches, copied out, and where `range` lies in them,
+/// for trying a patch without touching the file.
+fn scratch_copy(reader: &BitReader, range: &std::ops::Range<usize>) -> (Vec<u8>, std::ops::Range<usize>) {
+    let first_byte = range.start / 8;
+    let scratch = reader.data()[first_byte..range.end.div_ceil(8)].to_vec();
+    (scratch, range.start - first_byte * 8..range.end - first_byte * 8)
+}
+
+fn run_write(args: &Args, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, fields) = args.parse_positional()?;
+    let [field] = &fields[..] else {
+        return Err("Expected a single OFFSET and LENGTH with --write".into());
+    };
+    let input = open_for_patch(args, &file)?;
+    let output = Output {
+        order: args.order,
+        byte_order: args.byte_order,
+        format: args.format.clone(),
+        signed: args.signed,
+        bias: args.bias.clone(),
+        breakdown: false,
+    };
+    let byte_order = args
+        .byte_order
+        .unwrap_or_else(|| args.order.natural_byte_order());
+
+    let reader = BitReader::new(&input);
+    let range = reader.resolve_field(field, 0).map_err(|e| e.to_string())?;
+    let bits = range.len();
+    let value = parse_value(value).map_err(|e| e.to_string())?;
+    let encoded = match args.signed {
+        Some(encoding) => from_signed(&value, bits, encoding, args.bias.as_ref()),
+        None if value.sign() == Sign::Minus => {
+            from_signed(&value, bits, SignedEncoding::TwosComplement, None)
         }
-        file_bits - from_end
-    } else {
-        total_bits as usize
+        None => value.to_biguint(),
+    }
+    .ok_or_else(|| format!("{} doesn't fit in {} bits", value, bits))?;
+    let before = read_number(&reader, range.clone(), &output)?;
+    let raw_before = reader.read(range.clone(), args.order).map_err(|e| e.to_string())?;
+    if args.verbose {
+        eprintln!(
+            "File: {} bytes ({} bits)",
+            input.len(),
+            reader.len_bits()
+        );
+        print_pointers(
+            &reader
+                .pointers(field.offset.as_ref(), Some(&field.length))
+                .map_err(|e| e.to_string())?,
+        );
+    }
+
+    // Patch a copy of the bytes the field touches first: that checks the
+    // value fits, and gives the raw bits the journal records
+    let (mut scratch, scratch_range) = scratch_copy(&reader, &range);
+    let mut patched = BitWriter::new(&mut scratch);
+    patched
+        .write_with_byte_order(scratch_range.clone(), &encoded, args.order, byte_order)
+        .map_err(|e| e.to_string())?;
+    let after = read_number(&patched.reader(), scratch_range.clone(), &output)?;
+    let raw_after = patched
+        .reader()
+        .read(scratch_range, args.order)
+        .map_err(|e| e.to_string())?;
+
+    print_patch(
+        &range,
+        &format_number(&before, &output.format),
+        &format_number(&after, &output.format),
+        args.dry_run,
+    );
+    if args.dry_run {
+        return Ok(());
+    }
+
+    // Journal before patching, so a patch is never made without a record,
+    // and take the record back out if patching fails
+    let journal = journal_path(Path::new(&file));
+    let journal_error = |e: std::io::Error| format!("{}: {}", journal.display(), e);
+    let earlier = read_journal(&journal).map_err(journal_error)?;
+    append_journal(
+        &journal,
+        &JournalEntry {
+            start: range.start,
+            bits,
+            order: args.order,
+            before: raw_before,
+            after: raw_after,
+        },
+    )
+    .map_err(journal_error)?;
+    let patch = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let mut map = map_for_patch(&file)?;
+        BitWriter::new(&mut map)
+            .write_with_byte_order(range, &encoded, args.order, byte_order)
+            .map_err(|e| e.to_string())?;
+        map.flush().map_err(|e| format!("{}: {}", file, e))?;
+        Ok(())
+    })();
+    if let Err(e) = patch {
+        write_journal(&journal, &earlier).map_err(journal_error)?;
+        return Err(e);
+    }
+    if args.verbose {
+        eprintln!("Recorded in {}", journal.display());
+    }
+    Ok(())
+}
+
+fn run_undo(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let [file] = args.positional_args()[..] else {
+        return Err("Expected only FILE with --undo".into());
+    };
+    let journal = journal_path(Path::new(file));
+    let mut entries =
+        read_journal(&journal).map_err(|e| format!("{}: {}", journal.display(), e))?;
+    let Some(entry) = entries.pop() else {
+        return Err(format!("Nothing to undo: no patches recorded in {}", journal.display()).into());
     };
+    let input = open_for_patch(args, file)?;
 
-    let end_bit = offset_bits + bits as usize;
-    if end_bit > file_bits {
-        let excess_bits = end_bit - file_bits;
+    // Only undo a patch that's still in place
+    let now = BitReader::new(&input)
+        .read(entry.range(), entry.order)
+        .map_err(|e| e.to_string())?;
+    if now != entry.after {
         return Err(format!(
-            "Requested range exceeds file size: need bit {}, but file is {} bytes ({} bits) — {} bits past end",
-            end_bit - 1,
-            mmap.len(),
-            file_bits,
-            excess_bits
+            "{:#x}:{} [{} bits] is {:#x}, not the {:#x} written; not undoing",
+            entry.start / 8,
+            entry.start % 8,
+            entry.bits,
+            now,
+            entry.after
         )
         .into());
     }
 
-    if verbose {
-        let from_end = file_bits - offset_bits;
+    // Shown as --write shows them: in --format, with the byte order applied
+    let output = Output {
+        order: entry.order,
+        byte_order: args.byte_order,
+        format: args.format.clone(),
+        signed: args.signed,
+        bias: args.bias.clone(),
+        breakdown: false,
+    };
+    let reader = BitReader::new(&input);
+    let (mut scratch, scratch_range) = scratch_copy(&reader, &entry.range());
+    BitWriter::new(&mut scratch)
+        .write(scratch_range.clone(), &entry.before, entry.order)
+        .map_err(|e| e.to_string())?;
+    print_patch(
+        &entry.range(),
+        &format_number(&read_number(&reader, entry.range(), &output)?, &output.format),
+        &format_number(&read_number(&BitReader::new(&scratch), scratch_range, &output)?, &output.format),
+        args.dry_run,
+    );
+    if args.dry_run {
+        return Ok(());
+    }
+    let mut map = map_for_patch(file)?;
+    BitWriter::new(&mut map)
+        .write(entry.range(), &entry.before, entry.order)
+        .map_err(|e| e.to_string())?;
+    map.flush().map_err(|e| format!("{}: {}", file, e))?;
+    write_journal(&journal, &entries).map_err(|e| format!("{}: {}", journal.display(), e))?;
+    Ok(())
+}
+
+fn run_carve(args: &Args, out: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, fields) = args.parse_positional()?;
+    let [field] = &fields[..] else {
+        return Err("Expected a single OFFSET and LENGTH with --carve".into());
+    };
+    let input = open_input(args, &file)?;
+    let reader = BitReader::new(&input);
+    let range = reader.resolve_field(field, 0).map_err(|e| e.to_string())?;
+    if args.verbose {
         eprintln!(
             "File: {} bytes ({} bits)",
-            mmap.len(),
-            file_bits
+            input.len(),
+            reader.len_bits()
+        );
+        print_pointers(
+            &reader
+                .pointers(field.offset.as_ref(), Some(&field.length))
+                .map_err(|e| e.to_string())?,
         );
+    }
+
+    let chunks = reader
+        .aligned_chunks(range.clone(), args.order, args.pad, CHUNK_BYTES)
+        .map_err(|e| e.to_string())?;
+    let (bytes, padding) = (chunks.len_bytes(), chunks.padding_bits());
+    let fail = |e: std::io::Error| format!("{}: {}", out.display(), e);
+    let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(if out == Path::new("-") {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(std::fs::File::create(out).map_err(fail)?)
+    });
+    for chunk in chunks {
+        writer.write_all(&chunk).map_err(fail)?;
+    }
+    writer.flush().map_err(fail)?;
+
+    if args.verbose {
         eprintln!(
-            "Reading {} bits at offset {} ({:#x}) = ({} bytes, {} bits) = ({:#x}:{} bits) from end = -{}",
-            bits,
-            offset_bits,
-            offset_bits,
-            offset.bytes,
-            offset.bits,
-            offset.bytes,
-            offset.bits,
-            from_end
+            "Carved {} bits from {:#x}:{} to {}: {} bytes, {} padding bits",
+            range.len(),
+            range.start / 8,
+            range.start % 8,
+            out.display(),
+            bytes,
+            padding
+        );
+    }
+    Ok(())
+}
+
+/// Report statistics on FILE, or a range of it, as a whole or window by
+/// window.
+fn run_analyze(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, fields) = match args.positional_args()[..] {
+        [file] => (file.to_string(), Vec::new()),
+        [_, _] | [_, _, _] => args.parse_positional()?,
+        _ => {
+            return Err(
+                "Expected FILE, optionally with OFFSET and LENGTH or a range, with --analyze".into(),
+            );
+        }
+    };
+    let input = open_input(args, &file)?;
+    let reader = BitReader::new(&input);
+    let range = match fields.first() {
+        Some(field) => reader.resolve_field(field, 0).map_err(|e| e.to_string())?,
+        None => 0..reader.len_bits(),
+    };
+    let position = |bit: usize| format!("{:#x}:{}", bit / 8, bit % 8);
+
+    let Some(window) = &args.window else {
+        let stats = analyze(&reader, range.clone(), args.order).map_err(|e| e.to_string())?;
+        print_stats(&stats, &format!("{}..{}", position(range.start), position(range.end)));
+        return Ok(());
+    };
+    let length = |s: &str, what: &str| -> Result<usize, Box<dyn std::error::Error>> {
+        let bits = Length::from_str(s)
+            .map_err(|e| format!("Invalid {}: {}", what, e))?
+            .resolve_bits(&reader, range.start)
+            .map_err(|e| e.to_string())?;
+        match bits {
+            0 => Err(format!("The {} must be at least 1 bit", what).into()),
+            bits => Ok(bits as usize),
+        }
+    };
+    let window = length(window, "window")?;
+    let step = match &args.step {
+        Some(step) => length(step, "step")?,
+        None => window,
+    };
+    if window > range.len() {
+        return Err(format!(
+            "The window of {} bits is longer than the range of {} bits",
+            window,
+            range.len()
+        )
+        .into());
+    }
+
+    println!("offset\tentropy/byte\tentropy/bit\tones\truns/expected\tlongest run");
+    for start in (range.start..=range.end - window).step_by(step) {
+        let stats = analyze(&reader, start..start + window, args.order).map_err(|e| e.to_string())?;
+        println!(
+            "{}\t{:.4}\t{:.4}\t{:.2}%\t{:.3}\t{}",
+            position(start),
+            stats.byte_entropy(),
+            stats.bit_entropy(),
+            100.0 * stats.ones as f64 / stats.bits as f64,
+            stats.runs as f64 / stats.expected_runs(),
+            stats.longest_zeros.max(stats.longest_ones)
+        );
+    }
+    Ok(())
+}
+
+/// The full report on one range, described by `range`.
+fn print_stats(stats: &BitStats, range: &str) {
+    let percent = |n: u64, of: u64| 100.0 * n as f64 / of as f64;
+    println!("range: {} ({} bits, {} whole bytes)", range, stats.bits, stats.bytes());
+    println!(
+        "entropy: {:.4} bits/byte, {:.4} bits/bit",
+        stats.byte_entropy(),
+        stats.bit_entropy()
+    );
+    println!(
+        "popcount: {} ones, {} zeros ({:.2}% ones)",
+        stats.ones,
+        stats.bits - stats.ones,
+        percent(stats.ones, stats.bits)
+    );
+    let bias: Vec<String> = (0..8).map(|p| format!("{:.1}%", 100.0 * stats.bias(p))).collect();
+    println!("bit bias: {} (ones at each bit of a byte, first bit first)", bias.join(" "));
+    println!(
+        "runs: {} ({:.1} expected if random), mean {:.2} bits, longest {} zeros, {} ones",
+        stats.runs,
+        stats.expected_runs(),
+        stats.mean_run(),
+        stats.longest_zeros,
+        stats.longest_ones
+    );
+    println!("histogram:");
+    for (byte, &count) in stats.histogram.iter().enumerate().filter(|&(_, &n)| n > 0) {
+        println!("{:#04x}\t{}\t{:.2}%", byte, count, percent(count, stats.bytes()));
+    }
+}
+
+/// Bits compared at each candidate shift when looking for --diff-shift's
+/// best alignment.
+const SHIFT_PROBE_BITS: usize = 64 * 1024 * 8;
+
+fn run_diff(args: &Args, other: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (file, fields) = match args.positional_args()[..] {
+        [file] => (file.to_string(), Vec::new()),
+        [_, _] | [_, _, _] => args.parse_positional()?,
+        _ => {
+            return Err(
+                "Expected FILE, optionally with OFFSET and LENGTH or a range, with --diff".into(),
+            );
+        }
+    };
+    let a_input = open_input(args, &file)?;
+    let b_input = open_input(args, other)?;
+    let a = BitReader::new(&a_input);
+    let b = BitReader::new(&b_input);
+    let a_range = match fields.first() {
+        Some(field) => a.resolve_field(field, 0).map_err(|e| e.to_string())?,
+        None => 0..a.len_bits(),
+    };
+    let mut b_start = match &args.diff_offset {
+        Some(offset) => b
+            .resolve_offset(&Offset::from_str(offset).map_err(|e| format!("Invalid offset: {}", e))?)
+            .map_err(|e| e.to_string())?,
+        None => a_range.start,
+    };
+
+    if let Some(max) = args.diff_shift {
+        let (shift, differing) =
+            best_shift(&a, a_range.clone(), &b, b_start, max, SHIFT_PROBE_BITS, args.order)
+                .map_err(|e| e.to_string())?;
+        b_start = b_start.wrapping_add_signed(shift);
+        println!(
+            "Best alignment: {} shifted {:+} bits, to {:#x}:{} ({} bits differ in the first {} bits)",
+            other,
+            shift,
+            b_start / 8,
+            b_start % 8,
+            differing,
+            SHIFT_PROBE_BITS.min(a_range.len())
+        );
+    }
+
+    // Compare the whole of a given range, or as much as both files have
+    let b_end = if fields.is_empty() {
+        b.len_bits()
+    } else {
+        (b_start + a_range.len()).min(b.len_bits())
+    };
+    let b_range = b_start..b_end.max(b_start);
+    let common = a_range.len().min(b_range.len());
+    if args.verbose {
+        eprintln!(
+            "Comparing {} bits: {} from {:#x}:{}, {} from {:#x}:{}",
+            common,
+            file,
+            a_range.start / 8,
+            a_range.start % 8,
+            other,
+            b_range.start / 8,
+            b_range.start % 8
         );
     }
 
-    let value: BigUint = match order {
-        BitOrder::Msb => {
-            let bits: &BitSlice<u8, Msb0> = BitSlice::from_slice(&mmap[..]);
-            extract_bits_to_biguint(&bits[offset_bits..end_bit])
+    let runs = diff_ranges(&a, a_range.clone(), &b, b_range.clone(), args.order)
+        .map_err(|e| e.to_string())?;
+    let position = |bit: usize| format!("{:#x}:{}", bit / 8, bit % 8);
+    if matches!(args.format, OutputFormat::HexAscii) {
+        print_diff_dump(&a, a_range.start, &b, b_range.start, common, args.order)?;
+    } else {
+        // Ranges in FILE, in the START..END form ranges are read back with,
+        // and where they are in OTHER too if that's somewhere else
+        for run in &runs {
+            let other_at = if b_range.start == a_range.start {
+                String::new()
+            } else {
+                format!(
+                    "\t{}..{}",
+                    position(b_range.start + run.start),
+                    position(b_range.start + run.end)
+                )
+            };
+            println!(
+                "{}..{}\t{} bits{}",
+                position(a_range.start + run.start),
+                position(a_range.start + run.end),
+                run.len(),
+                other_at
+            );
+        }
+    }
+
+    // Bits past the end of the shorter side
+    let extra = if a_range.len() > common {
+        Some((&file, a_range.start + common..a_range.end))
+    } else if b_range.len() > common {
+        Some((&other.to_string(), b_range.start + common..b_range.end))
+    } else {
+        None
+    };
+    if let Some((name, range)) = &extra {
+        println!(
+            "{}..{}\t{} bits only in {}",
+            position(range.start),
+            position(range.end),
+            range.len(),
+            name
+        );
+    }
+
+    if args.verbose {
+        eprintln!(
+            "{} differing bits in {} ranges",
+            runs.iter().map(|run| run.len()).sum::<usize>(),
+            runs.len()
+        );
+    }
+    if !runs.is_empty() || extra.is_some() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Hexdump rows of `a` and `b` side by side, `-` then `+`, for each row
+/// where they differ, with the changed bits highlighted. Runs of identical
+/// rows show as `*`.
+fn print_diff_dump(
+    a: &BitReader,
+    a_start: usize,
+    b: &BitReader,
+    b_start: usize,
+    len: usize,
+    order: BitOrder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let a_chunks = a
+        .aligned_chunks(a_start..a_start + len, order, Padding::Zero, CHUNK_BYTES)
+        .map_err(|e| e.to_string())?;
+    let b_chunks = b
+        .aligned_chunks(b_start..b_start + len, order, Padding::Zero, CHUNK_BYTES)
+        .map_err(|e| e.to_string())?;
+    let offset_width = format!("{:x}", (a_start.max(b_start) + len) / 8).len();
+    let width = best_fit_width(get_terminal_width().unwrap_or(80), offset_width as u16 + 2) as usize;
+
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    let mut row = 0;
+    let mut skipped = false;
+    for (a_chunk, b_chunk) in a_chunks.zip(b_chunks) {
+        // Chunks hold whole rows at every width, so rows never straddle them
+        for (a_row, b_row) in a_chunk.chunks(width).zip(b_chunk.chunks(width)) {
+            let changed: Vec<u8> = a_row.iter().zip(b_row).map(|(x, y)| x ^ y).collect();
+            if changed.iter().all(|&m| m == 0) {
+                if !skipped {
+                    writeln!(out, "*")?;
+                    skipped = true;
+                }
+            } else {
+                skipped = false;
+                for (sign, bytes, start) in [("-", a_row, a_start), ("+", b_row, b_start)] {
+                    write!(out, "{} ", sign)?;
+                    let offset = (start / 8 + row * width) as u64;
+                    print_hex_ascii_changes(&mut out, bytes, Some(&changed), offset, width, offset_width)?;
+                }
+            }
+            row += 1;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// A number in --format: hex unless decimal or binary is asked for.
+fn format_number(value: &BigInt, format: &OutputFormat) -> String {
+    match format {
+        OutputFormat::Decimal => value.to_string(),
+        OutputFormat::Binary => format!("{:#b}", value),
+        _ => format!("{:#x}", value),
+    }
+}
+
+/// Read a field as --signed says: signed, or as an unsigned number.
+fn read_number(
+    reader: &BitReader,
+    range: std::ops::Range<usize>,
+    output: &Output,
+) -> Result<BigInt, Box<dyn std::error::Error>> {
+    let bits = range.len();
+    let value = match output.byte_order {
+        Some(byte_order) => reader.read_with_byte_order(range, output.order, byte_order),
+        None => reader.read(range, output.order),
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(match output.signed {
+        Some(encoding) => to_signed(&value, bits, encoding, output.bias.as_ref()),
+        None => BigInt::from(value),
+    })
+}
+
+fn print_field(
+    reader: &BitReader,
+    range: std::ops::Range<usize>,
+    label: Option<&str>,
+    output: &Output,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bits = range.len();
+    let offset_bits = range.start;
+    let format = &output.format;
+
+    if let Some(float_format) = format.float_format()
+        && bits != float_format.width()
+    {
+        return Err(ReadError::WrongWidth {
+            bits,
+            expected: float_format.width(),
         }
-        BitOrder::Lsb => {
-            let bits: &BitSlice<u8, Lsb0> = BitSlice::from_slice(&mmap[..]);
-            extract_bits_to_biguint_lsb(&bits[offset_bits..end_bit])
+        .to_string()
+        .into());
+    }
+
+    // Single-line formats put the label in front; multi-line ones above
+    let multi_line = matches!(format, OutputFormat::HexAscii) || output.breakdown;
+    let print_label = || {
+        if let Some(label) = label {
+            if multi_line {
+                println!("{}", label);
+            } else {
+                print!("{}\t", label);
+            }
+        }
+    };
+
+    // Byte formats stream straight from the data, however long the field
+    if matches!(format, OutputFormat::Ascii | OutputFormat::HexAscii) {
+        let byte_order = output
+            .byte_order
+            .unwrap_or_else(|| output.order.natural_byte_order());
+        let chunks = reader
+            .byte_chunks(range, output.order, byte_order, CHUNK_BYTES)
+            .map_err(|e| e.to_string())?;
+        print_label();
+        return print_bytes(chunks, offset_bits / 8, format);
+    }
+
+    let value: BigUint = match output.byte_order {
+        Some(byte_order) => reader.read_with_byte_order(range, output.order, byte_order),
+        None => reader.read(range, output.order),
+    }
+    .map_err(|e| e.to_string())?;
+
+    // Numeric formats honour --signed; byte formats always show the raw bits
+    let number = |value: &BigUint| -> BigInt {
+        match output.signed {
+            Some(encoding) => to_signed(value, bits, encoding, output.bias.as_ref()),
+            None => BigInt::from(value.clone()),
         }
     };
 
-    // For text formats, pad bytes to match the requested bit length
-    let num_bytes = (bits as usize + 7) / 8;
+    print_label();
 
     match format {
-        OutputFormat::Decimal => println!("{}", value),
-        OutputFormat::Hex => println!("{:#x}", value),
-        OutputFormat::Binary => println!("{:#b}", value),
-        OutputFormat::Ascii => {
-            let mut bytes = value.to_bytes_be();
-            // Pad with leading zeros if needed
-            while bytes.len() < num_bytes {
-                bytes.insert(0, 0);
+        OutputFormat::Decimal => println!("{}", number(&value)),
+        OutputFormat::Hex => println!("{:#x}", number(&value)),
+        OutputFormat::Binary => println!("{:#b}", number(&value)),
+        OutputFormat::Ascii | OutputFormat::HexAscii => unreachable!("streamed above"),
+        OutputFormat::Binary16
+        | OutputFormat::Bfloat16
+        | OutputFormat::Binary32
+        | OutputFormat::Binary64
+        | OutputFormat::Binary128
+        | OutputFormat::X87 => {
+            let float_format = format.float_format().expect("float output format");
+            let parts = decode_float(&value, float_format);
+            if output.breakdown {
+                print_float_breakdown(&parts);
+            }
+            println!("{}", parts);
+        }
+    }
+
+    Ok(())
+}
+
+/// One field for --output: where it is and its value in every form.
+#[derive(Serialize)]
+struct FieldRecord {
+    file: String,
+    offset: usize,
+    /// `bytes:bits`, as OFFSET accepts
+    position: String,
+    from_end: usize,
+    bits: usize,
+    bit_order: String,
+    byte_order: String,
+    hex: String,
+    decimal: String,
+    binary: String,
+    /// With --signed's encoding, or two's complement
+    signed: String,
+    /// Only with a float --format
+    #[serde(skip_serializing_if = "Option::is_none")]
+    float: Option<String>,
+    /// Only with --batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+impl FieldRecord {
+    /// Column names and values, in order, for CSV.
+    fn columns(&self) -> Vec<(&'static str, String)> {
+        let mut columns = vec![
+            ("file", self.file.clone()),
+            ("offset", self.offset.to_string()),
+            ("position", self.position.clone()),
+            ("from_end", self.from_end.to_string()),
+            ("bits", self.bits.to_string()),
+            ("bit_order", self.bit_order.clone()),
+            ("byte_order", self.byte_order.clone()),
+            ("hex", self.hex.clone()),
+            ("decimal", self.decimal.clone()),
+            ("binary", self.binary.clone()),
+            ("signed", self.signed.clone()),
+            ("float", self.float.clone().unwrap_or_default()),
+        ];
+        if let Some(label) = &self.label {
+            columns.push(("label", label.clone()));
+        }
+        columns
+    }
+}
+
+fn field_record(
+    reader: &BitReader,
+    file: &str,
+    range: std::ops::Range<usize>,
+    output: &Output,
+) -> Result<FieldRecord, Box<dyn std::error::Error>> {
+    let bits = range.len();
+    let name = |value: Option<clap::builder::PossibleValue>| {
+        value.map_or(String::new(), |v| v.get_name().to_string())
+    };
+    let byte_order = output
+        .byte_order
+        .unwrap_or_else(|| output.order.natural_byte_order());
+    let float = match output.format.float_format() {
+        Some(format) if bits != format.width() => {
+            return Err(ReadError::WrongWidth {
+                bits,
+                expected: format.width(),
             }
-            print_ascii(&bytes);
+            .to_string()
+            .into());
         }
-        OutputFormat::HexAscii => {
-            let mut bytes = value.to_bytes_be();
-            // Pad with leading zeros if needed
-            while bytes.len() < num_bytes {
-                bytes.insert(0, 0);
+        Some(format) => Some(format),
+        None => None,
+    };
+    let value = reader
+        .read_with_byte_order(range.clone(), output.order, byte_order)
+        .map_err(|e| e.to_string())?;
+    let signed = to_signed(
+        &value,
+        bits,
+        output.signed.unwrap_or(SignedEncoding::TwosComplement),
+        output.bias.as_ref(),
+    );
+
+    Ok(FieldRecord {
+        file: file.to_string(),
+        offset: range.start,
+        position: format!("{:#x}:{}", range.start / 8, range.start % 8),
+        from_end: reader.len_bits() - range.start,
+        bits,
+        bit_order: name(output.order.to_possible_value()),
+        byte_order: name(byte_order.to_possible_value()),
+        hex: format!("{:#x}", value),
+        decimal: value.to_string(),
+        binary: format!("{:#b}", value),
+        signed: signed.to_string(),
+        float: float.map(|format| decode_float(&value, format).to_string()),
+        label: None,
+    })
+}
+
+/// Prints records for --output: NDJSON and CSV as they come, JSON as one
+/// array at the end.
+struct RecordWriter {
+    mode: OutputMode,
+    pending: Vec<FieldRecord>,
+    wrote_header: bool,
+}
+
+impl RecordWriter {
+    fn new(mode: OutputMode) -> Self {
+        RecordWriter {
+            mode,
+            pending: Vec::new(),
+            wrote_header: false,
+        }
+    }
+
+    fn write(&mut self, record: FieldRecord) -> Result<(), Box<dyn std::error::Error>> {
+        match self.mode {
+            OutputMode::Text => unreachable!("text output prints fields directly"),
+            OutputMode::Json => self.pending.push(record),
+            OutputMode::Ndjson => println!("{}", serde_json::to_string(&record)?),
+            OutputMode::Csv => {
+                let columns = record.columns();
+                if !self.wrote_header {
+                    let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+                    println!("{}", names.join(","));
+                    self.wrote_header = true;
+                }
+                let values: Vec<String> = columns.iter().map(|(_, value)| csv_quote(value)).collect();
+                println!("{}", values.join(","));
             }
-            
-            // Determine width and calculate offset field width
-            let term_width = get_terminal_width().unwrap_or(80) as u16;
-            
-            // Calculate hex digits needed for maximum offset
-            let max_offset_bits = offset_bits + bytes.len() * 8;
-            let max_offset_bytes = max_offset_bits / 8;
-            let offset_hex_width = format!("{:x}", max_offset_bytes).len();
-            
-            // Recalculate with real offset width
-            let width = best_fit_width(term_width, offset_hex_width as u16);
-            
-            print_hex_ascii(&bytes, offset_bits as u64, width as usize, offset_hex_width);
         }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.mode == OutputMode::Json {
+            println!("{}", serde_json::to_string_pretty(&self.pending)?);
+        }
+        Ok(())
+    }
+}
+
+/// Quote a CSV value if it holds a comma, quote or line break.
+fn csv_quote(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn print_float_breakdown(parts: &FloatParts) {
+    let format = parts.format;
+    println!("format: {} ({} bits)", format.name(), format.width());
+    println!("sign: {} ({})", parts.sign as u8, if parts.sign { "-" } else { "+" });
+    match parts.unbiased_exponent() {
+        Some(unbiased) => println!(
+            "exponent: {:#x} ({} biased, {} unbiased)",
+            parts.exponent, parts.exponent, unbiased
+        ),
+        None => println!("exponent: {:#x} ({} biased)", parts.exponent, parts.exponent),
     }
+    println!(
+        "mantissa: {:#0width$x}",
+        parts.mantissa,
+        width = format.mantissa_bits().div_ceil(4) + 2
+    );
+    println!("class: {}", parts.class);
+}
+
+/// Print a field's bytes as ASCII or a hex dump, a chunk at a time.
+/// `start_offset` is the byte offset shown for the first row.
+fn print_bytes(
+    chunks: ByteChunks,
+    start_offset: usize,
+    format: &OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = BufWriter::new(std::io::stdout().lock());
+    if let OutputFormat::HexAscii = format {
+        // Determine width and calculate offset field width
+        let term_width = get_terminal_width().unwrap_or(80);
+
+        // Calculate hex digits needed for maximum offset
+        let max_offset_bytes = start_offset + chunks.len_bytes();
+        let offset_hex_width = format!("{:x}", max_offset_bytes).len();
 
+        // Recalculate with real offset width
+        let width = best_fit_width(term_width, offset_hex_width as u16) as usize;
+
+        let mut offset = start_offset;
+        for chunk in chunks {
+            print_hex_ascii(&mut out, &chunk, offset as u64, width, offset_hex_width)?;
+            offset += chunk.len();
+        }
+    } else {
+        for chunk in chunks {
+            print_ascii(&mut out, &chunk)?;
+        }
+        writeln!(out)?;
+    }
+    out.flush()?;
     Ok(())
 }
 
-fn print_ascii(bytes: &[u8]) {
-    for &b in bytes {
+fn print_ascii(out: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    print_ascii_changes(out, bytes, None)
+}
+
+/// ANSI: bold red for changed bits
+const CHANGED: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Print bytes as ASCII, with those that have bits set in `changed` (one
+/// mask byte per byte) highlighted.
+fn print_ascii_changes(
+    out: &mut impl Write,
+    bytes: &[u8],
+    changed: Option<&[u8]>,
+) -> std::io::Result<()> {
+    for (i, &b) in bytes.iter().enumerate() {
+        let highlight = changed.is_some_and(|mask| mask[i] != 0);
+        if highlight {
+            write!(out, "{}", CHANGED)?;
+        }
         if b.is_ascii_graphic() || b == b' ' {
-            print!("{}", b as char);
+            write!(out, "{}", b as char)?;
         } else {
             // ANSI: red background for non-printable
-            print!("\x1b[41m \x1b[0m");
+            write!(out, "\x1b[41m \x1b[0m")?;
+        }
+        if highlight {
+            write!(out, "{}", RESET)?;
         }
     }
-    println!();
+    Ok(())
 }
 
-fn print_hex_ascii(bytes: &[u8], start_offset: u64, width: usize, offset_width: usize) {
+fn print_hex_ascii(
+    out: &mut impl Write,
+    bytes: &[u8],
+    start_offset: u64,
+    width: usize,
+    offset_width: usize,
+) -> std::io::Result<()> {
+    print_hex_ascii_changes(out, bytes, None, start_offset, width, offset_width)
+}
+
+/// [`print_hex_ascii`], highlighting the hex digits with bits set in
+/// `changed` (one mask byte per byte) and the matching ASCII.
+fn print_hex_ascii_changes(
+    out: &mut impl Write,
+    bytes: &[u8],
+    changed: Option<&[u8]>,
+    start_offset: u64,
+    width: usize,
+    offset_width: usize,
+) -> std::io::Result<()> {
     // Print chunks with offset field (hexdump style)
     for (i, chunk) in bytes.chunks(width).enumerate() {
         let chunk_offset = start_offset + (i * width) as u64;
-        
+        let mask = changed.map(|mask| &mask[i * width..i * width + chunk.len()]);
+
         // Print offset field (0-padded hex, no 0x prefix)
-        print!("{:0width$x}: ", chunk_offset, width = offset_width);
-        
-        // Print hex bytes
-        for &b in chunk {
-            print!("{:02x} ", b);
+        write!(out, "{:0width$x}: ", chunk_offset, width = offset_width)?;
+
+        // Print hex bytes, a digit at a time so a changed nibble stands out
+        for (j, &b) in chunk.iter().enumerate() {
+            let m = mask.map_or(0, |mask| mask[j]);
+            for (digit, nibble) in [(b >> 4, m >> 4), (b & 0xf, m & 0xf)] {
+                if nibble != 0 {
+                    write!(out, "{}{:x}{}", CHANGED, digit, RESET)?;
+                } else {
+                    write!(out, "{:x}", digit)?;
+                }
+            }
+            write!(out, " ")?;
         }
-        
+
         // Padding to align ASCII column
         if chunk.len() < width {
             for _ in chunk.len()..width {
-                print!("   ");
+                write!(out, "   ")?;
             }
         }
-        
+
         // Separator
-        print!("| ");
-        
+        write!(out, "| ")?;
+
         // Print ASCII
-        for &b in chunk {
-            if b.is_ascii_graphic() || b == b' ' {
-                print!("{}", b as char);
-            } else {
-                // ANSI: red background for non-printable
-                print!("\x1b[41m \x1b[0m");
-            }
-        }
-        println!();
+        print_ascii_changes(out, chunk, mask)?;
+        writeln!(out)?;
     }
+    Ok(())
 }
@@ -0,0 +1,295 @@
+//! Parsing of bit offsets and bit lengths.
+//!
+//! Both accept decimal or hex (`0x1A`, `$1A`, `1Ah`) numbers with optional
+//! thousands separators (`,` `_` `'`). A bare number counts bits; a
+//! `bytes:bits` (or `bytes.bits`) pair counts bytes plus 0-7 extra bits.
+//! Offsets may be negative, meaning "from the end".
+//!
+//! A length can also be `*`, for the rest of the data.
+//!
+//! Either can also be an expression such as `end-0x20:3`, `(0x40+12)*8b` or
+//! `@[0x3c:32le]`; see the `expr` module for units, symbols and pointers.
+
+use crate::expr::Expr;
+use crate::reader::{BitReader, Pointer, ReadError};
+use num_bigint::{BigInt, BigUint};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OffsetError {
+    ParseError(String),
+    InvalidBitOffset,
+}
+
+impl std::fmt::Display for OffsetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OffsetError::ParseError(msg) => write!(f, "Invalid offset: {}", msg),
+            OffsetError::InvalidBitOffset => write!(f, "Bit offset must be 0-7"),
+        }
+    }
+}
+
+impl std::error::Error for OffsetError {}
+
+/// Parse a single unsigned number in decimal or one of the hex notations.
+pub(crate) fn parse_number(num_str: &str) -> Result<u64, OffsetError> {
+    if num_str.starts_with("0x") || num_str.starts_with("0X") {
+        u64::from_str_radix(&num_str[2..], 16)
+    } else if let Some(hex) = num_str.strip_prefix('$') {
+        u64::from_str_radix(hex, 16)
+    } else if num_str.ends_with('h') || num_str.ends_with('H') {
+        u64::from_str_radix(&num_str[..num_str.len() - 1], 16)
+    } else {
+        num_str.parse::<u64>()
+    }
+    .map_err(|e| OffsetError::ParseError(e.to_string()))
+}
+
+/// Parse an optionally negative number of any size, in decimal, binary
+/// (`0b`) or any of the hex notations, with the separators offsets take;
+/// also returns the radix it was written in. The one parser for values,
+/// biases and the numbers in structure definitions.
+///
+/// ```
+/// use itty_bitty::parse_signed;
+/// use num_bigint::BigInt;
+///
+/// assert_eq!(parse_signed("-0x1_00"), Some((BigInt::from(-256), 16)));
+/// assert_eq!(parse_signed("0b101"), Some((BigInt::from(5), 2)));
+/// assert_eq!(parse_signed("1,024"), Some((BigInt::from(1024), 10)));
+/// assert_eq!(parse_signed("--1"), None);
+/// ```
+pub fn parse_signed(s: &str) -> Option<(BigInt, u32)> {
+    let s = s.trim();
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, s),
+    };
+    let digits = digits.replace(&[',', '_', '\''][..], "");
+    let (digits, radix) = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        (hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        (bin, 2)
+    } else if let Some(hex) = digits.strip_prefix('$') {
+        (hex, 16)
+    } else if let Some(hex) = digits.strip_suffix(['h', 'H']) {
+        (hex, 16)
+    } else {
+        (digits.as_str(), 10)
+    };
+    // No second sign
+    if !digits.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let value = BigInt::from(BigUint::parse_bytes(digits.as_bytes(), radix)?);
+    Some((if negative { -value } else { value }, radix))
+}
+
+/// Split `bytes:bits` / `bytes.bits` into the number part and the bit count.
+pub(crate) fn split_bits(s: &str) -> Result<(&str, Option<u32>), OffsetError> {
+    if let Some((a, b)) = s.split_once([':', '.']) {
+        let bits = b
+            .parse::<u32>()
+            .map_err(|_| OffsetError::ParseError("Invalid bit count".into()))?;
+        if bits > 7 {
+            return Err(OffsetError::InvalidBitOffset);
+        }
+        Ok((a, Some(bits)))
+    } else {
+        Ok((s, None))
+    }
+}
+
+/// A number of bits to read, or the offset a field runs up to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Length {
+    /// The length when it's a plain number; meaningless when an expression
+    /// or an end offset is set, as [`needs_data`](Self::needs_data) tells
+    pub bits: u64,
+    /// An expression that needs the data, evaluated by
+    /// [`resolve_bits`](Self::resolve_bits); `bits` is then unused
+    expr: Option<Expr>,
+    /// For `*` and `START..END`: where the field ends, and whether the bit
+    /// there is included
+    until: Option<(Offset, bool)>,
+}
+
+impl Length {
+    /// A plain length of `bits` bits.
+    pub fn from_bits(bits: u64) -> Self {
+        Length {
+            bits,
+            expr: None,
+            until: None,
+        }
+    }
+
+    /// A field that runs up to `end`, or through it if `inclusive`.
+    pub fn until(end: Offset, inclusive: bool) -> Self {
+        Length {
+            bits: 0,
+            expr: None,
+            until: Some((end, inclusive)),
+        }
+    }
+
+    /// A field that runs to the end of the data, written `*`.
+    pub fn rest() -> Self {
+        Length::until(
+            Offset {
+                expr: Some(Expr::Size),
+                ..Offset::from_bits(0)
+            },
+            false,
+        )
+    }
+
+    /// The length in bits. Lengths that depend on the data count as 0 here;
+    /// see [`resolve_bits`](Self::resolve_bits).
+    pub fn to_bits(&self) -> u64 {
+        self.bits
+    }
+
+    /// Whether the length uses `end`, `size` or a pointer, or runs up to an
+    /// offset.
+    pub fn needs_data(&self) -> bool {
+        self.expr.is_some() || self.until.is_some()
+    }
+
+    /// Where a field starting at bit `start` ends, if that doesn't depend on
+    /// the data.
+    pub(crate) fn fixed_end(&self, start: u64) -> Option<u64> {
+        match &self.until {
+            _ if self.expr.is_some() => None,
+            None => start.checked_add(self.bits),
+            Some((end, inclusive)) if !end.is_negative && !end.needs_data() => {
+                Some(end.to_bits() as u64 + *inclusive as u64)
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// The length in bits of a field starting at bit `start` of `reader`'s
+    /// data.
+    pub fn resolve_bits(&self, reader: &BitReader, start: usize) -> Result<u64, ReadError> {
+        self.eval(reader, start, &mut Vec::new())
+    }
+
+    pub(crate) fn eval(
+        &self,
+        reader: &BitReader,
+        start: usize,
+        chain: &mut Vec<Pointer>,
+    ) -> Result<u64, ReadError> {
+        if let Some((end, inclusive)) = &self.until {
+            let end = reader.absolute_offset(end.eval(reader, chain)?)? + *inclusive as usize;
+            return match end.checked_sub(start) {
+                Some(bits) => Ok(bits as u64),
+                None => Err(ReadError::BackwardRange { start, end }),
+            };
+        }
+        match &self.expr {
+            Some(expr) => u64::try_from(expr.eval(Some(reader), chain)?).map_err(|_| {
+                ReadError::Expression(OffsetError::ParseError("length is negative".into()))
+            }),
+            None => Ok(self.bits),
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = OffsetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim() == "*" {
+            return Ok(Length::rest());
+        }
+        let expr = Expr::parse(s)?;
+        if expr.needs_data() {
+            return Ok(Length {
+                expr: Some(expr),
+                ..Length::from_bits(0)
+            });
+        }
+        let bits = u64::try_from(expr.eval_const()?)
+            .map_err(|_| OffsetError::ParseError("length is negative".into()))?;
+        Ok(Length::from_bits(bits))
+    }
+}
+
+/// A bit position, either from the start or (if negative) from the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offset {
+    /// Whole bytes of a plain offset. This and the next two fields are
+    /// meaningless when an expression is set, as
+    /// [`needs_data`](Self::needs_data) tells
+    pub bytes: u64,
+    /// Bits past `bytes`, 0-7
+    pub bits: u32,
+    /// Whether the offset counts back from the end of the data
+    pub is_negative: bool,
+    /// An expression that needs the data, evaluated by
+    /// [`resolve_bits`](Self::resolve_bits); the other fields are then unused
+    expr: Option<Expr>,
+}
+
+impl Offset {
+    /// A plain offset of `bits` bits, counting back from the end of the
+    /// data if negative.
+    pub fn from_bits(bits: i64) -> Self {
+        let abs = bits.unsigned_abs();
+        Offset {
+            bytes: abs / 8,
+            bits: (abs % 8) as u32,
+            is_negative: bits < 0,
+            expr: None,
+        }
+    }
+
+    /// The offset in bits, negative from the end. Offsets that depend on the
+    /// data count as 0 here; see [`resolve_bits`](Self::resolve_bits).
+    pub fn to_bits(&self) -> i64 {
+        let total_bits = (self.bytes * 8) as i64 + self.bits as i64;
+        if self.is_negative { -total_bits } else { total_bits }
+    }
+
+    /// Whether the offset uses `end`, `size` or a pointer.
+    pub fn needs_data(&self) -> bool {
+        self.expr.is_some()
+    }
+
+    /// The offset in bits into `reader`'s data, negative from the end as for
+    /// [`to_bits`](Self::to_bits).
+    pub fn resolve_bits(&self, reader: &BitReader) -> Result<i64, ReadError> {
+        self.eval(reader, &mut Vec::new())
+    }
+
+    pub(crate) fn eval(&self, reader: &BitReader, chain: &mut Vec<Pointer>) -> Result<i64, ReadError> {
+        match &self.expr {
+            Some(expr) => i64::try_from(expr.eval(Some(reader), chain)?).map_err(|_| {
+                ReadError::Expression(OffsetError::ParseError("offset out of range".into()))
+            }),
+            None => Ok(self.to_bits()),
+        }
+    }
+}
+
+impl FromStr for Offset {
+    type Err = OffsetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expr = Expr::parse(s)?;
+        if expr.needs_data() {
+            return Ok(Offset {
+                bytes: 0,
+                bits: 0,
+                is_negative: false,
+                expr: Some(expr),
+            });
+        }
+        let bits = i64::try_from(expr.eval_const()?)
+            .map_err(|_| OffsetError::ParseError("offset out of range".into()))?;
+        Ok(Offset::from_bits(bits))
+    }
+}
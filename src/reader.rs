@@ -0,0 +1,588 @@
+//! A bit-addressable view over a byte slice (or anything that derefs to one,
+//! such as a memory map).
+
+use crate::extract::{
+    extract_bits_to_biguint, extract_bits_to_biguint_lsb, extract_bits_to_u128,
+    extract_bits_to_u128_lsb, reorder_bytes,
+};
+use crate::field::FieldSpec;
+use crate::float::{FloatFormat, FloatParts, decode_float};
+use crate::offset::{Length, Offset, OffsetError};
+use crate::signed::{SignedEncoding, to_signed};
+use crate::{BitOrder, ByteOrder};
+use bitvec::prelude::*;
+use num_bigint::{BigInt, BigUint};
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    /// Zero-length reads are rejected.
+    EmptyRead,
+    /// A negative offset reaches back past the start of the data.
+    NegativeOffsetTooLarge { from_end: u64, total_bits: usize },
+    /// The requested range runs past the end of the data.
+    PastEnd {
+        end_bit: usize,
+        total_bytes: usize,
+        total_bits: usize,
+    },
+    /// The value does not fit the requested native integer type.
+    TooWide { bits: usize, max: usize },
+    /// A fixed-width interpretation was asked of a field of another width.
+    WrongWidth { bits: usize, expected: usize },
+    /// A byte order was asked of a field that isn't made of whole words.
+    NotWholeWords { bits: usize, word_bits: usize },
+    /// A range ends before it starts.
+    BackwardRange { start: usize, end: usize },
+    /// An `after:` or `before:` marker didn't occur often enough.
+    MarkerNotFound { marker: String, matches: usize },
+    /// An offset or length expression couldn't be evaluated.
+    Expression(OffsetError),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::EmptyRead => write!(f, "Must read at least 1 bit"),
+            ReadError::NegativeOffsetTooLarge {
+                from_end,
+                total_bits,
+            } => write!(
+                f,
+                "Negative offset -{} exceeds file size ({} bits)",
+                from_end, total_bits
+            ),
+            ReadError::PastEnd {
+                end_bit,
+                total_bytes,
+                total_bits,
+            } => write!(
+                f,
+                "Requested range exceeds file size: need bit {}, but file is {} bytes ({} bits) — {} bits past end",
+                end_bit - 1,
+                total_bytes,
+                total_bits,
+                end_bit - total_bits
+            ),
+            ReadError::TooWide { bits, max } => {
+                write!(f, "Cannot fit {} bits into a {}-bit integer", bits, max)
+            }
+            ReadError::WrongWidth { bits, expected } => {
+                write!(f, "Expected a {}-bit field, got {} bits", expected, bits)
+            }
+            ReadError::NotWholeWords { bits, word_bits } => write!(
+                f,
+                "Byte order needs a multiple of {} bits, got {} bits",
+                word_bits, bits
+            ),
+            ReadError::BackwardRange { start, end } => write!(
+                f,
+                "Range ends at bit {} ({:#x}:{}), before its start at bit {} ({:#x}:{})",
+                end,
+                end / 8,
+                end % 8,
+                start,
+                start / 8,
+                start % 8
+            ),
+            ReadError::MarkerNotFound { marker, matches } => write!(
+                f,
+                "Marker {} not found ({} matches in the data)",
+                marker, matches
+            ),
+            ReadError::Expression(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+/// A pointer followed while resolving an `@[OFFSET:BITS]` expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    /// Absolute bit offset of the pointer field
+    pub at: usize,
+    pub bits: usize,
+    pub byte_order: ByteOrder,
+    /// The byte offset read
+    pub value: u64,
+}
+
+/// Reads bitfields from a byte slice.
+///
+/// ```
+/// use itty_bitty::{BitOrder, BitReader, Length, Offset};
+///
+/// let data = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+/// let reader = BitReader::new(&data);
+/// let offset: Offset = "-0:4".parse().unwrap();
+/// let length: Length = "4".parse().unwrap();
+/// let range = reader.resolve(&offset, &length).unwrap();
+/// assert_eq!(range, 44..48);
+/// assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 9);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BitReader<'a> {
+    /// A reader over `data`, bit 0 being the first bit of its first byte.
+    pub fn new(data: &'a [u8]) -> Self {
+        BitReader { data }
+    }
+
+    /// The underlying bytes.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Total number of bits available.
+    pub fn len_bits(&self) -> usize {
+        self.data.len() * 8
+    }
+
+    /// Resolve a possibly negative offset to an absolute bit position.
+    pub fn resolve_offset(&self, offset: &Offset) -> Result<usize, ReadError> {
+        self.absolute_offset(offset.resolve_bits(self)?)
+    }
+
+    /// Turn a bit offset that is negative from the end into an absolute one.
+    pub(crate) fn absolute_offset(&self, total_bits: i64) -> Result<usize, ReadError> {
+        let file_bits = self.len_bits();
+        if total_bits < 0 {
+            let from_end = total_bits.unsigned_abs();
+            if from_end > file_bits as u64 {
+                return Err(ReadError::NegativeOffsetTooLarge {
+                    from_end,
+                    total_bits: file_bits,
+                });
+            }
+            Ok(file_bits - from_end as usize)
+        } else {
+            Ok(total_bits as usize)
+        }
+    }
+
+    /// Resolve an offset and length to an absolute, bounds-checked bit range.
+    pub fn resolve(&self, offset: &Offset, length: &Length) -> Result<Range<usize>, ReadError> {
+        let start = self.resolve_offset(offset)?;
+        let bits = length.resolve_bits(self, start)? as usize;
+        if bits == 0 {
+            return Err(ReadError::EmptyRead);
+        }
+        self.check_range(start..start.saturating_add(bits))
+    }
+
+    /// The pointers an offset and length follow, in the order they are read.
+    pub fn pointers(&self, offset: Option<&Offset>, length: Option<&Length>) -> Result<Vec<Pointer>, ReadError> {
+        let mut chain = Vec::new();
+        let start = match offset {
+            Some(offset) => self.absolute_offset(offset.eval(self, &mut chain)?)?,
+            None => 0,
+        };
+        if let Some(length) = length {
+            length.eval(self, start, &mut chain)?;
+        }
+        Ok(chain)
+    }
+
+    /// Resolve a field list to bit ranges, chaining fields that have no
+    /// offset onto the end of the previous field.
+    pub fn resolve_fields(&self, fields: &[FieldSpec]) -> Result<Vec<Range<usize>>, ReadError> {
+        let mut cursor = 0;
+        fields
+            .iter()
+            .map(|field| {
+                let range = self.resolve_field(field, cursor)?;
+                cursor = range.end;
+                Ok(range)
+            })
+            .collect()
+    }
+
+    /// Resolve one field to a bit range; a field with no offset starts at
+    /// bit `cursor`.
+    pub fn resolve_field(&self, field: &FieldSpec, cursor: usize) -> Result<Range<usize>, ReadError> {
+        match &field.offset {
+            Some(offset) => self.resolve(offset, &field.length),
+            None => self.resolve(&Offset::from_bits(cursor as i64), &field.length),
+        }
+    }
+
+    /// Check that `range` lies within the data.
+    pub fn check_range(&self, range: Range<usize>) -> Result<Range<usize>, ReadError> {
+        if range.end > self.len_bits() {
+            return Err(ReadError::PastEnd {
+                end_bit: range.end,
+                total_bytes: self.data.len(),
+                total_bits: self.len_bits(),
+            });
+        }
+        Ok(range)
+    }
+
+    /// Read the bits in `range` as an unsigned integer of any width.
+    ///
+    /// With [`BitOrder::Msb`] bits are numbered from the top of each byte and
+    /// the first bit read is the most significant; with [`BitOrder::Lsb`]
+    /// bits are numbered from the bottom and the first bit read is the least
+    /// significant.
+    pub fn read(&self, range: Range<usize>, order: BitOrder) -> Result<BigUint, ReadError> {
+        let range = self.check_range(range)?;
+        Ok(match order {
+            BitOrder::Msb => {
+                let bits: &BitSlice<u8, Msb0> = BitSlice::from_slice(self.data);
+                extract_bits_to_biguint(&bits[range])
+            }
+            BitOrder::Lsb => {
+                let bits: &BitSlice<u8, Lsb0> = BitSlice::from_slice(self.data);
+                extract_bits_to_biguint_lsb(&bits[range])
+            }
+        })
+    }
+
+    /// Read the bits in `range` with an explicit byte order.
+    ///
+    /// Each consecutive 8 bits form one byte, assembled with `order`; the
+    /// bytes are then arranged by significance according to `byte_order`.
+    /// Other than big-endian MSB and little-endian LSB reads, the range must
+    /// be whole words.
+    pub fn read_with_byte_order(
+        &self,
+        range: Range<usize>,
+        order: BitOrder,
+        byte_order: ByteOrder,
+    ) -> Result<BigUint, ReadError> {
+        // These pairs are what a plain read does, and work for any width
+        if matches!(
+            (order, byte_order),
+            (BitOrder::Msb, ByteOrder::Big) | (BitOrder::Lsb, ByteOrder::Little)
+        ) {
+            return self.read(range, order);
+        }
+        let range = self.check_range(range)?;
+        let word_bits = byte_order.unit_bytes() * 8;
+        if range.len() % word_bits != 0 {
+            return Err(ReadError::NotWholeWords {
+                bits: range.len(),
+                word_bits,
+            });
+        }
+        let mut bytes: Vec<u8> = match order {
+            BitOrder::Msb => {
+                let bits: &BitSlice<u8, Msb0> = BitSlice::from_slice(self.data);
+                bits[range].chunks(8).map(|b| b.load_be::<u8>()).collect()
+            }
+            BitOrder::Lsb => {
+                let bits: &BitSlice<u8, Lsb0> = BitSlice::from_slice(self.data);
+                bits[range].chunks(8).map(|b| b.load_le::<u8>()).collect()
+            }
+        };
+        reorder_bytes(&mut bytes, byte_order);
+        Ok(BigUint::from_bytes_be(&bytes))
+    }
+
+    /// The bytes [`read_with_byte_order`](Self::read_with_byte_order) would
+    /// return for `range`, most significant first, produced `chunk_bytes` at
+    /// a time straight from the data so that huge ranges need no `BigUint`.
+    ///
+    /// A field that isn't whole bytes is padded with zero bits at the top,
+    /// as the integer would be.
+    pub fn byte_chunks(
+        &self,
+        range: Range<usize>,
+        order: BitOrder,
+        byte_order: ByteOrder,
+        chunk_bytes: usize,
+    ) -> Result<ByteChunks<'a>, ReadError> {
+        let range = self.check_range(range)?;
+        let native = matches!(
+            (order, byte_order),
+            (BitOrder::Msb, ByteOrder::Big) | (BitOrder::Lsb, ByteOrder::Little)
+        );
+        let word_bits = byte_order.unit_bytes() * 8;
+        if !native && range.len() % word_bits != 0 {
+            return Err(ReadError::NotWholeWords {
+                bits: range.len(),
+                word_bits,
+            });
+        }
+        // Keep whole words in every chunk so each can be reordered alone
+        let unit = byte_order.unit_bytes();
+        let chunk_bytes = chunk_bytes.max(unit) / unit * unit;
+        Ok(ByteChunks {
+            reader: *self,
+            range,
+            order,
+            byte_order,
+            chunk_bytes,
+            done: 0,
+        })
+    }
+
+    /// The bits in `range` shifted to start on a byte boundary, produced
+    /// `chunk_bytes` at a time: a copy of the bitstream rather than the
+    /// integer it holds.
+    ///
+    /// With [`BitOrder::Msb`] the first bit lands at the top of the first
+    /// byte; with [`BitOrder::Lsb`] at the bottom. The unused bits of a final
+    /// partial byte are filled as `padding` says.
+    pub fn aligned_chunks(
+        &self,
+        range: Range<usize>,
+        order: BitOrder,
+        padding: Padding,
+        chunk_bytes: usize,
+    ) -> Result<AlignedChunks<'a>, ReadError> {
+        let range = self.check_range(range)?;
+        Ok(AlignedChunks {
+            reader: *self,
+            range,
+            order,
+            padding,
+            chunk_bytes: chunk_bytes.max(1),
+            done: 0,
+        })
+    }
+
+    /// Read the bits in `range` as a signed integer in the given encoding.
+    pub fn read_signed(
+        &self,
+        range: Range<usize>,
+        order: BitOrder,
+        encoding: SignedEncoding,
+    ) -> Result<BigInt, ReadError> {
+        let width = range.len();
+        let value = self.read(range, order)?;
+        Ok(to_signed(&value, width, encoding, None))
+    }
+
+    /// Read the bits in `range` as a float; the range must be exactly as
+    /// wide as the format.
+    pub fn read_float(
+        &self,
+        range: Range<usize>,
+        order: BitOrder,
+        format: FloatFormat,
+    ) -> Result<FloatParts, ReadError> {
+        if range.len() != format.width() {
+            return Err(ReadError::WrongWidth {
+                bits: range.len(),
+                expected: format.width(),
+            });
+        }
+        let value = self.read(range, order)?;
+        Ok(decode_float(&value, format))
+    }
+
+    /// Read the bits in `range` into a `u64`.
+    pub fn read_u64(&self, range: Range<usize>, order: BitOrder) -> Result<u64, ReadError> {
+        let width = range.len();
+        if width > 64 {
+            return Err(ReadError::TooWide { bits: width, max: 64 });
+        }
+        Ok(self.read_u128(range, order)? as u64)
+    }
+
+    /// Read the bits in `range` into a `u128`.
+    pub fn read_u128(&self, range: Range<usize>, order: BitOrder) -> Result<u128, ReadError> {
+        let width = range.len();
+        if width > 128 {
+            return Err(ReadError::TooWide {
+                bits: width,
+                max: 128,
+            });
+        }
+        let range = self.check_range(range)?;
+        Ok(match order {
+            BitOrder::Msb => {
+                let bits: &BitSlice<u8, Msb0> = BitSlice::from_slice(self.data);
+                extract_bits_to_u128(&bits[range])
+            }
+            BitOrder::Lsb => {
+                let bits: &BitSlice<u8, Lsb0> = BitSlice::from_slice(self.data);
+                extract_bits_to_u128_lsb(&bits[range])
+            }
+        })
+    }
+}
+
+/// Iterator over a field's bytes in chunks; see [`BitReader::byte_chunks`].
+#[derive(Debug, Clone)]
+pub struct ByteChunks<'a> {
+    reader: BitReader<'a>,
+    range: Range<usize>,
+    order: BitOrder,
+    byte_order: ByteOrder,
+    chunk_bytes: usize,
+    /// Output bytes already produced
+    done: usize,
+}
+
+impl ByteChunks<'_> {
+    /// Total number of bytes the iterator produces.
+    pub fn len_bytes(&self) -> usize {
+        self.range.len().div_ceil(8)
+    }
+
+    /// Fill `out` with the field's bytes in stream order, starting at byte
+    /// `first`: each assembled from 8 bits with the bit order, and a short
+    /// byte zero-extended (first for MSB, last for LSB).
+    fn fill_stream_bytes(&self, first: usize, out: &mut [u8]) {
+        let data = self.reader.data;
+        let Range { start, end } = self.range;
+        match self.order {
+            BitOrder::Msb => {
+                let bits: &BitSlice<u8, Msb0> = BitSlice::from_slice(data);
+                let pad = self.len_bytes() * 8 - self.range.len();
+                for (i, byte) in out.iter_mut().enumerate() {
+                    // Position in the padded field, then in the data
+                    let pos = (first + i) * 8;
+                    let b = (start + pos).saturating_sub(pad);
+                    *byte = if pos < pad {
+                        bits[start..start + 8 - pad].load_be::<u8>()
+                    } else if b.is_multiple_of(8) {
+                        data[b / 8]
+                    } else {
+                        let shift = b % 8;
+                        data[b / 8] << shift | data[b / 8 + 1] >> (8 - shift)
+                    };
+                }
+            }
+            BitOrder::Lsb => {
+                let bits: &BitSlice<u8, Lsb0> = BitSlice::from_slice(data);
+                for (i, byte) in out.iter_mut().enumerate() {
+                    let b = start + (first + i) * 8;
+                    *byte = if b + 8 > end {
+                        bits[b..end].load_le::<u8>()
+                    } else if b.is_multiple_of(8) {
+                        data[b / 8]
+                    } else {
+                        let shift = b % 8;
+                        data[b / 8] >> shift | data[b / 8 + 1] << (8 - shift)
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for ByteChunks<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let total = self.len_bytes();
+        if self.done >= total {
+            return None;
+        }
+        let len = self.chunk_bytes.min(total - self.done);
+        // Output bytes done..done+len come from one run of stream bytes:
+        // the same run for orders that keep words in place, the mirror-image
+        // run for those that reverse them
+        let first = match self.byte_order {
+            ByteOrder::Big | ByteOrder::Pdp => self.done,
+            _ => total - self.done - len,
+        };
+        let mut chunk = vec![0u8; len];
+        self.fill_stream_bytes(first, &mut chunk);
+        reorder_bytes(&mut chunk, self.byte_order);
+        self.done += len;
+        Some(chunk)
+    }
+}
+
+/// How to fill the unused bits of a final partial byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Padding {
+    /// Zero bits
+    #[cfg_attr(feature = "cli", value(alias = "zeros"))]
+    Zero,
+    /// One bits
+    #[cfg_attr(feature = "cli", value(alias = "ones"))]
+    One,
+}
+
+/// `zero` (`zeros`) or `one` (`ones`).
+impl std::str::FromStr for Padding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_name(
+            s,
+            "padding",
+            &[
+                ("zero", Padding::Zero),
+                ("zeros", Padding::Zero),
+                ("one", Padding::One),
+                ("ones", Padding::One),
+            ],
+        )
+    }
+}
+
+/// Iterator over a bit range realigned to bytes, in chunks; see
+/// [`BitReader::aligned_chunks`].
+#[derive(Debug, Clone)]
+pub struct AlignedChunks<'a> {
+    reader: BitReader<'a>,
+    range: Range<usize>,
+    order: BitOrder,
+    padding: Padding,
+    chunk_bytes: usize,
+    /// Output bytes already produced
+    done: usize,
+}
+
+impl AlignedChunks<'_> {
+    /// Total number of bytes the iterator produces.
+    pub fn len_bytes(&self) -> usize {
+        self.range.len().div_ceil(8)
+    }
+
+    /// Number of padding bits in the final byte.
+    pub fn padding_bits(&self) -> usize {
+        self.len_bytes() * 8 - self.range.len()
+    }
+}
+
+impl Iterator for AlignedChunks<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let total = self.len_bytes();
+        if self.done >= total {
+            return None;
+        }
+        let len = self.chunk_bytes.min(total - self.done);
+        let data = self.reader.data;
+        let shift = self.range.start % 8;
+        let first = self.range.start / 8 + self.done;
+        // Each output byte straddles two stream bytes unless already aligned
+        let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+        let mut chunk: Vec<u8> = (first..first + len)
+            .map(|i| match (self.order, shift) {
+                (_, 0) => data[i],
+                (BitOrder::Msb, s) => byte(i) << s | byte(i + 1) >> (8 - s),
+                (BitOrder::Lsb, s) => byte(i) >> s | byte(i + 1) << (8 - s),
+            })
+            .collect();
+        self.done += len;
+
+        let pad = self.padding_bits();
+        if self.done == total && pad > 0 {
+            // Unused bits are the bottom ones for MSB, the top for LSB
+            let unused = match self.order {
+                BitOrder::Msb => 0xffu8 >> (8 - pad),
+                BitOrder::Lsb => 0xffu8 << (8 - pad),
+            };
+            let last = chunk.last_mut().expect("at least one byte");
+            *last = match self.padding {
+                Padding::Zero => *last & !unused,
+                Padding::One => *last | unused,
+            };
+        }
+        Some(chunk)
+    }
+}
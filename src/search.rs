@@ -0,0 +1,236 @@
+//! Searching for bit patterns at any bit offset.
+
+use crate::reader::BitReader;
+use crate::BitOrder;
+use bitvec::prelude::*;
+use num_bigint::BigUint;
+use std::ops::Range;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    /// `""`, which would match anywhere.
+    EmptyText,
+    /// Not a pattern at all; holds the text as given.
+    Invalid(String),
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::EmptyText => write!(f, "Empty text pattern"),
+            PatternError::Invalid(s) => write!(f, "Invalid pattern '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// A value to search for, with an explicit width in bits and a mask of the
+/// bits that must match.
+///
+/// Hex (`0x0F`) and binary (`0b0001`) patterns are as wide as their digits,
+/// leading zeros included; decimal patterns are as wide as the value. In hex
+/// and binary, `x` or `?` is a don't-care digit (`0b1111_xxxx`, `0xFFF?`).
+/// A `/MASK` suffix keeps only the mask's set bits (`0xFFF0/0xFFF0`). Text
+/// in double quotes (`"IEND"`) stands for its bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub value: BigUint,
+    /// Bits that must match; clear bits are don't-cares
+    pub mask: BigUint,
+    pub bits: usize,
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(text) = s.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+            if text.is_empty() {
+                return Err(PatternError::EmptyText);
+            }
+            return Ok(Pattern::from_bytes(text.as_bytes()));
+        }
+        let s = s.replace(&[',', '_', '\''][..], "");
+        let invalid = || PatternError::Invalid(s.clone());
+        let (value_part, mask_part) = match s.split_once('/') {
+            Some((value, mask)) => (value, Some(mask)),
+            None => (s.as_str(), None),
+        };
+
+        let (digits, bits_per_digit) = if let Some(hex) =
+            value_part.strip_prefix("0x").or_else(|| value_part.strip_prefix("0X"))
+        {
+            (hex, 4)
+        } else if let Some(bin) =
+            value_part.strip_prefix("0b").or_else(|| value_part.strip_prefix("0B"))
+        {
+            (bin, 1)
+        } else {
+            (value_part, 0)
+        };
+        if digits.is_empty() {
+            return Err(invalid());
+        }
+
+        let (value, mut mask, bits) = if bits_per_digit == 0 {
+            let value = BigUint::parse_bytes(digits.as_bytes(), 10).ok_or_else(invalid)?;
+            let bits = (value.bits() as usize).max(1);
+            (value, ones(bits), bits)
+        } else {
+            let radix = 1 << bits_per_digit;
+            let digit_mask = BigUint::from(radix - 1);
+            let mut value = BigUint::ZERO;
+            let mut mask = BigUint::ZERO;
+            for c in digits.chars() {
+                value <<= bits_per_digit;
+                mask <<= bits_per_digit;
+                if matches!(c, 'x' | 'X' | '?') {
+                    continue;
+                }
+                value |= BigUint::from(c.to_digit(radix).ok_or_else(invalid)?);
+                mask |= &digit_mask;
+            }
+            (value, mask, digits.len() * bits_per_digit)
+        };
+
+        if let Some(mask_part) = mask_part {
+            let explicit: Pattern = mask_part.parse()?;
+            if explicit.mask != ones(explicit.bits) {
+                return Err(invalid());
+            }
+            mask &= explicit.value & ones(bits);
+        }
+
+        Ok(Pattern {
+            value: value & &mask,
+            mask,
+            bits,
+        })
+    }
+}
+
+fn ones(bits: usize) -> BigUint {
+    (BigUint::from(1u8) << bits) - 1u8
+}
+
+impl Pattern {
+    /// A pattern matching `bytes` exactly.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let bits = bytes.len() * 8;
+        Pattern {
+            value: BigUint::from_bytes_be(bytes),
+            mask: ones(bits),
+            bits,
+        }
+    }
+
+    /// The pattern's bits in the order they are met in the stream: most
+    /// significant first for [`BitOrder::Msb`], least significant first for
+    /// [`BitOrder::Lsb`].
+    pub fn stream_bits(&self, order: BitOrder) -> BitVec<u8, Msb0> {
+        stream_order(&self.value, self.bits, order)
+    }
+
+    /// The mask's bits in stream order, as for [`Pattern::stream_bits`].
+    pub fn stream_mask(&self, order: BitOrder) -> BitVec<u8, Msb0> {
+        stream_order(&self.mask, self.bits, order)
+    }
+}
+
+fn stream_order(n: &BigUint, bits: usize, order: BitOrder) -> BitVec<u8, Msb0> {
+    let bit = |i: usize| n.bit(i as u64);
+    match order {
+        BitOrder::Msb => (0..bits).rev().map(bit).collect(),
+        BitOrder::Lsb => (0..bits).map(bit).collect(),
+    }
+}
+
+/// Find every bit offset where `pattern` occurs, keeping only offsets that
+/// are a multiple of `align` bits.
+pub fn search(reader: &BitReader, pattern: &Pattern, order: BitOrder, align: usize) -> Vec<usize> {
+    search_range(reader, pattern, order, align, 0..reader.len_bits())
+}
+
+/// Like [`search`], but only for matches lying entirely within `range`.
+/// Offsets stay absolute, and so does the alignment.
+pub fn search_range(
+    reader: &BitReader,
+    pattern: &Pattern,
+    order: BitOrder,
+    align: usize,
+    range: Range<usize>,
+) -> Vec<usize> {
+    let needle = pattern.stream_bits(order);
+    let care = pattern.stream_mask(order);
+    let range = range.start.min(reader.len_bits())..range.end.min(reader.len_bits());
+    match order {
+        BitOrder::Msb => find(
+            &BitSlice::<u8, Msb0>::from_slice(reader.data())[range.clone()],
+            &needle,
+            &care,
+            align,
+            range.start,
+        ),
+        BitOrder::Lsb => find(
+            &BitSlice::<u8, Lsb0>::from_slice(reader.data())[range.clone()],
+            &needle,
+            &care,
+            align,
+            range.start,
+        ),
+    }
+}
+
+/// Find `needle` (where `care` is set) in `haystack`, which starts at bit
+/// `base` of the data; returned offsets include `base`.
+fn find<O: bitvec::order::BitOrder>(
+    haystack: &BitSlice<u8, O>,
+    needle: &BitSlice<u8, Msb0>,
+    care: &BitSlice<u8, Msb0>,
+    align: usize,
+    base: usize,
+) -> Vec<usize> {
+    let n = needle.len();
+    let align = align.max(1);
+    let mut found = Vec::new();
+    if n == 0 || n > haystack.len() {
+        return found;
+    }
+
+    // Slide a 64-bit window over the stream and compare the needle's first
+    // (up to) 64 bits; longer needles are then checked in full
+    let k = n.min(64);
+    let to_u64 = |bits: &BitSlice<u8, Msb0>| bits.iter().by_vals().fold(0u64, |acc, b| acc << 1 | b as u64);
+    let prefix = to_u64(&needle[..k]);
+    let prefix_care = to_u64(&care[..k]);
+    let mut window = 0u64;
+    for (i, bit) in haystack.iter().by_vals().enumerate() {
+        window = window << 1 | bit as u64;
+        if i + 1 < k {
+            continue;
+        }
+        let start = i + 1 - k;
+        if window & prefix_care == prefix
+            && (base + start).is_multiple_of(align)
+            && start + n <= haystack.len()
+            && (n == k || matches_at(&haystack[start..start + n], needle, care))
+        {
+            found.push(base + start);
+        }
+    }
+    found
+}
+
+fn matches_at<O: bitvec::order::BitOrder>(
+    bits: &BitSlice<u8, O>,
+    needle: &BitSlice<u8, Msb0>,
+    care: &BitSlice<u8, Msb0>,
+) -> bool {
+    bits.iter()
+        .by_vals()
+        .zip(needle.iter().by_vals())
+        .zip(care.iter().by_vals())
+        .all(|((bit, want), care)| !care || bit == want)
+}
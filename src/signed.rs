@@ -0,0 +1,142 @@
+//! Signed interpretations of an extracted field.
+//!
+//! Extraction always yields the raw bits as an unsigned number, with the
+//! most significant bit of the field at bit `width - 1` whichever
+//! [`BitOrder`](crate::BitOrder) was used, so these conversions work the same
+//! for both orders and for any width.
+
+use num_bigint::{BigInt, BigUint, Sign};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SignedEncoding {
+    /// Two's complement: the top bit weighs -2^(width-1)
+    #[cfg_attr(feature = "cli", value(alias = "twos"))]
+    TwosComplement,
+    /// One's complement: negatives are the bitwise inverse of the magnitude
+    #[cfg_attr(feature = "cli", value(alias = "ones"))]
+    OnesComplement,
+    /// Sign-magnitude: top bit is the sign, the rest is the magnitude
+    #[cfg_attr(feature = "cli", value(alias = "sign-mag"))]
+    SignMagnitude,
+    /// Offset binary (excess-K): the stored value minus a bias
+    #[cfg_attr(feature = "cli", value(alias = "excess"))]
+    OffsetBinary,
+}
+
+/// `twos-complement` (`twos`), `ones-complement` (`ones`), `sign-magnitude`
+/// (`sign-mag`) or `offset-binary` (`excess`).
+impl std::str::FromStr for SignedEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_name(
+            s,
+            "signed encoding",
+            &[
+                ("twos-complement", SignedEncoding::TwosComplement),
+                ("twos", SignedEncoding::TwosComplement),
+                ("ones-complement", SignedEncoding::OnesComplement),
+                ("ones", SignedEncoding::OnesComplement),
+                ("sign-magnitude", SignedEncoding::SignMagnitude),
+                ("sign-mag", SignedEncoding::SignMagnitude),
+                ("offset-binary", SignedEncoding::OffsetBinary),
+                ("excess", SignedEncoding::OffsetBinary),
+            ],
+        )
+    }
+}
+
+/// The default excess-K bias for offset binary: 2^(width-1).
+pub fn default_bias(width: usize) -> BigInt {
+    BigInt::from(1u8) << width.saturating_sub(1)
+}
+
+/// Reinterpret the low `width` bits of `value` as a signed number.
+///
+/// `bias` is only used by [`SignedEncoding::OffsetBinary`]; `None` means
+/// [`default_bias`]. Negative zero (one's complement and sign-magnitude)
+/// comes out as plain zero.
+pub fn to_signed(
+    value: &BigUint,
+    width: usize,
+    encoding: SignedEncoding,
+    bias: Option<&BigInt>,
+) -> BigInt {
+    if width == 0 {
+        return BigInt::ZERO;
+    }
+    let sign = value.bit(width as u64 - 1);
+    let unsigned = BigInt::from(value.clone());
+    match encoding {
+        SignedEncoding::TwosComplement => {
+            if sign {
+                unsigned - (BigInt::from(1u8) << width)
+            } else {
+                unsigned
+            }
+        }
+        SignedEncoding::OnesComplement => {
+            if sign {
+                unsigned - ((BigInt::from(1u8) << width) - 1u8)
+            } else {
+                unsigned
+            }
+        }
+        SignedEncoding::SignMagnitude => {
+            if sign {
+                -(unsigned - (BigInt::from(1u8) << (width - 1)))
+            } else {
+                unsigned
+            }
+        }
+        SignedEncoding::OffsetBinary => match bias {
+            Some(bias) => unsigned - bias,
+            None => unsigned - default_bias(width),
+        },
+    }
+}
+
+/// Encode `value` in `width` bits with the given signed encoding: the
+/// inverse of [`to_signed`]. Returns `None` if the value doesn't fit.
+pub fn from_signed(
+    value: &BigInt,
+    width: usize,
+    encoding: SignedEncoding,
+    bias: Option<&BigInt>,
+) -> Option<BigUint> {
+    if width == 0 {
+        return None;
+    }
+    let modulus = BigInt::from(1u8) << width;
+    let half = BigInt::from(1u8) << (width - 1);
+    let negative = value.sign() == Sign::Minus;
+    let raw = match encoding {
+        SignedEncoding::TwosComplement => {
+            if *value < -&half || *value >= half {
+                return None;
+            }
+            if negative { value + &modulus } else { value.clone() }
+        }
+        SignedEncoding::OnesComplement => {
+            if value.magnitude() >= half.magnitude() {
+                return None;
+            }
+            if negative { value + &modulus - 1u8 } else { value.clone() }
+        }
+        SignedEncoding::SignMagnitude => {
+            if value.magnitude() >= half.magnitude() {
+                return None;
+            }
+            if negative { &half - value } else { value.clone() }
+        }
+        SignedEncoding::OffsetBinary => match bias {
+            Some(bias) => value + bias,
+            None => value + default_bias(width),
+        },
+    };
+    if raw.sign() == Sign::Minus || raw >= modulus {
+        return None;
+    }
+    raw.to_biguint()
+}
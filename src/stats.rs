@@ -0,0 +1,156 @@
+//! Statistics over a bit range: entropy, popcount, per-position bias, runs
+//! and a byte histogram, for telling compressed or encrypted data from
+//! structure and spotting misalignment.
+//!
+//! The range is realigned to bytes with
+//! [`BitReader::aligned_chunks`](crate::BitReader::aligned_chunks), so the
+//! bytes counted are those that start at the range's first bit, wherever it
+//! lies.
+
+use crate::{BitOrder, CHUNK_BYTES};
+use crate::reader::{BitReader, Padding, ReadError};
+use std::ops::Range;
+
+/// What [`analyze`] finds in a bit range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitStats {
+    pub bits: u64,
+    /// Set bits in the whole range
+    pub ones: u64,
+    /// How often each byte value occurs, over the whole bytes of the range;
+    /// a final partial byte isn't counted
+    pub histogram: [u64; 256],
+    /// Set bits at each bit of a realigned byte, first bit first
+    pub position_ones: [u64; 8],
+    /// Bits at each bit of a realigned byte, first bit first
+    pub position_bits: [u64; 8],
+    /// Runs of equal bits
+    pub runs: u64,
+    pub longest_zeros: u64,
+    pub longest_ones: u64,
+}
+
+impl BitStats {
+    /// How many whole bytes the histogram counts.
+    pub fn bytes(&self) -> u64 {
+        self.histogram.iter().sum()
+    }
+
+    /// Shannon entropy of the byte values, from 0 to 8 bits per byte.
+    pub fn byte_entropy(&self) -> f64 {
+        entropy(&self.histogram, self.bytes())
+    }
+
+    /// Shannon entropy of the bits, from 0 to 1 bit per bit.
+    pub fn bit_entropy(&self) -> f64 {
+        entropy(&[self.ones, self.bits - self.ones], self.bits)
+    }
+
+    /// The fraction of bits at `position` of a realigned byte that are set.
+    pub fn bias(&self, position: usize) -> f64 {
+        match self.position_bits[position] {
+            0 => 0.0,
+            bits => self.position_ones[position] as f64 / bits as f64,
+        }
+    }
+
+    pub fn mean_run(&self) -> f64 {
+        match self.runs {
+            0 => 0.0,
+            runs => self.bits as f64 / runs as f64,
+        }
+    }
+
+    /// How many runs random bits with as many ones would have on average.
+    /// Far fewer than this means long repeats; far more, alternating bits.
+    pub fn expected_runs(&self) -> f64 {
+        if self.bits == 0 {
+            return 0.0;
+        }
+        let (ones, zeros) = (self.ones as f64, (self.bits - self.ones) as f64);
+        1.0 + 2.0 * ones * zeros / self.bits as f64
+    }
+}
+
+/// Shannon entropy in bits of a distribution given as counts out of `total`.
+fn entropy(counts: &[u64], total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let total = total as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            // Not -p * log2(p), which gives -0.0 for a single value
+            let p = n as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum()
+}
+
+/// Gather [`BitStats`] for the bits in `range`, which may start and end
+/// anywhere.
+///
+/// ```
+/// use itty_bitty::{BitOrder, BitReader, analyze};
+///
+/// let data = [0x0f, 0x0f, 0x00];
+/// let stats = analyze(&BitReader::new(&data), 4..20, BitOrder::Msb).unwrap();
+/// assert_eq!((stats.ones, stats.runs), (8, 4));
+/// assert_eq!(stats.histogram[0xf0], 2);
+/// assert_eq!(stats.byte_entropy(), 0.0);
+/// ```
+pub fn analyze(reader: &BitReader, range: Range<usize>, order: BitOrder) -> Result<BitStats, ReadError> {
+    if range.is_empty() {
+        return Err(ReadError::EmptyRead);
+    }
+    let chunks = reader.aligned_chunks(range.clone(), order, Padding::Zero, CHUNK_BYTES)?;
+    let whole_bytes = (range.len() / 8) as u64;
+    let mut stats = BitStats {
+        bits: range.len() as u64,
+        ones: 0,
+        histogram: [0; 256],
+        position_ones: [0; 8],
+        position_bits: [0; 8],
+        runs: 0,
+        longest_zeros: 0,
+        longest_ones: 0,
+    };
+    let mut last = None;
+    let mut run = 0u64;
+    let mut index = 0u64;
+    for chunk in chunks {
+        for byte in chunk {
+            // Only the final byte can be partial, and its padding comes last
+            let bits = if index < whole_bytes {
+                stats.histogram[byte as usize] += 1;
+                8
+            } else {
+                range.len() % 8
+            };
+            index += 1;
+            for position in 0..bits {
+                let set = match order {
+                    BitOrder::Msb => byte & (0x80 >> position) != 0,
+                    BitOrder::Lsb => byte & (1 << position) != 0,
+                };
+                stats.position_bits[position] += 1;
+                if set {
+                    stats.position_ones[position] += 1;
+                    stats.ones += 1;
+                }
+                if last == Some(set) {
+                    run += 1;
+                } else {
+                    stats.runs += 1;
+                    run = 1;
+                    last = Some(set);
+                }
+                let longest = if set { &mut stats.longest_ones } else { &mut stats.longest_zeros };
+                *longest = (*longest).max(run);
+            }
+        }
+    }
+    Ok(stats)
+}
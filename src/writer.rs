@@ -0,0 +1,291 @@
+//! Patching bits in place, and the undo journal that records each patch.
+
+use crate::extract::reorder_bytes;
+use crate::reader::{BitReader, ReadError};
+use crate::{BitOrder, ByteOrder};
+use bitvec::prelude::*;
+use memmap2::{MmapMut, MmapOptions};
+use num_bigint::{BigInt, BigUint};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    /// The range can't be read, so can't be written either.
+    Range(ReadError),
+    /// The value needs more bits than the field has.
+    ValueTooWide { value_bits: u64, bits: usize },
+    /// Not a value to write; holds the text as given.
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Range(e) => write!(f, "{}", e),
+            WriteError::ValueTooWide { value_bits, bits } => write!(
+                f,
+                "Value needs {} bits but the field is only {} bits",
+                value_bits, bits
+            ),
+            WriteError::InvalidValue(s) => write!(f, "Invalid value '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl From<ReadError> for WriteError {
+    fn from(e: ReadError) -> Self {
+        WriteError::Range(e)
+    }
+}
+
+/// A bit-addressable mutable view over a byte slice, writing fields the way
+/// [`BitReader`] reads them. Bits outside the field are left untouched.
+///
+/// ```
+/// use itty_bitty::{BitOrder, BitWriter};
+/// use num_bigint::{BigInt, BigUint};
+///
+/// let mut data = [0xffu8; 2];
+/// BitWriter::new(&mut data).write(4..12, &BigUint::from(0x5au8), BitOrder::Msb).unwrap();
+/// assert_eq!(data, [0xf5, 0xaf]);
+/// ```
+#[derive(Debug)]
+pub struct BitWriter<'a> {
+    data: &'a mut [u8],
+}
+
+impl<'a> BitWriter<'a> {
+    pub fn new(data: &'a mut [u8]) -> Self {
+        BitWriter { data }
+    }
+
+    /// A reader over the same data, to resolve offsets or read what's there.
+    pub fn reader(&self) -> BitReader<'_> {
+        BitReader::new(self.data)
+    }
+
+    /// Write `value` to the bits in `range`, numbered and ordered as
+    /// [`BitReader::read`] reads them.
+    pub fn write(
+        &mut self,
+        range: Range<usize>,
+        value: &BigUint,
+        order: BitOrder,
+    ) -> Result<(), WriteError> {
+        let range = self.check(range, value)?;
+        let width = range.len() as u64;
+        match order {
+            BitOrder::Msb => {
+                let bits: &mut BitSlice<u8, Msb0> = BitSlice::from_slice_mut(self.data);
+                for (i, mut bit) in bits[range].iter_mut().enumerate() {
+                    *bit = value.bit(width - 1 - i as u64);
+                }
+            }
+            BitOrder::Lsb => {
+                let bits: &mut BitSlice<u8, Lsb0> = BitSlice::from_slice_mut(self.data);
+                for (i, mut bit) in bits[range].iter_mut().enumerate() {
+                    *bit = value.bit(i as u64);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `value` to the bits in `range` as
+    /// [`BitReader::read_with_byte_order`] reads them.
+    pub fn write_with_byte_order(
+        &mut self,
+        range: Range<usize>,
+        value: &BigUint,
+        order: BitOrder,
+        byte_order: ByteOrder,
+    ) -> Result<(), WriteError> {
+        if matches!(
+            (order, byte_order),
+            (BitOrder::Msb, ByteOrder::Big) | (BitOrder::Lsb, ByteOrder::Little)
+        ) {
+            return self.write(range, value, order);
+        }
+        let range = self.check(range, value)?;
+        let word_bits = byte_order.unit_bytes() * 8;
+        if range.len() % word_bits != 0 {
+            return Err(ReadError::NotWholeWords {
+                bits: range.len(),
+                word_bits,
+            }
+            .into());
+        }
+        // Big-endian bytes of the value (which fits, so needs no more than
+        // the field has), arranged as they're stored; every byte order is
+        // its own inverse
+        let digits = value.to_bytes_be();
+        let mut bytes = vec![0u8; range.len() / 8];
+        let skip = bytes.len() - digits.len();
+        bytes[skip..].copy_from_slice(&digits);
+        reorder_bytes(&mut bytes, byte_order);
+        match order {
+            BitOrder::Msb => {
+                let bits: &mut BitSlice<u8, Msb0> = BitSlice::from_slice_mut(self.data);
+                for (chunk, byte) in bits[range].chunks_mut(8).zip(bytes) {
+                    chunk.store_be(byte);
+                }
+            }
+            BitOrder::Lsb => {
+                let bits: &mut BitSlice<u8, Lsb0> = BitSlice::from_slice_mut(self.data);
+                for (chunk, byte) in bits[range].chunks_mut(8).zip(bytes) {
+                    chunk.store_le(byte);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that `range` lies within the data and `value` fits in it.
+    fn check(&self, range: Range<usize>, value: &BigUint) -> Result<Range<usize>, WriteError> {
+        if range.is_empty() {
+            return Err(ReadError::EmptyRead.into());
+        }
+        let range = self.reader().check_range(range)?;
+        if value.bits() > range.len() as u64 {
+            return Err(WriteError::ValueTooWide {
+                value_bits: value.bits(),
+                bits: range.len(),
+            });
+        }
+        Ok(range)
+    }
+}
+
+/// Parse a value to write: a number in decimal, hex (`0x`) or binary
+/// (`0b`), possibly negative, or quoted text for its bytes, first byte most
+/// significant.
+pub fn parse_value(s: &str) -> Result<BigInt, WriteError> {
+    crate::expect::parse_value(s)
+        .map(|(value, _)| value)
+        .map_err(|_| WriteError::InvalidValue(s.trim().to_string()))
+}
+
+/// Map `path` for writing, so patches go straight to the file.
+pub fn map_writable(path: &Path) -> io::Result<MmapMut> {
+    let file = OpenOptions::new().read(true).write(true).open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a regular file"));
+    }
+    // Safety: as with any mapped file, another process changing it at the
+    // same time gives undefined results
+    unsafe { MmapOptions::new().map_mut(&file) }
+}
+
+/// One patch in an undo journal: the raw bits of the field before and
+/// after, read with the patch's bit order and its natural byte order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Bit offset of the field, numbered with `order`
+    pub start: usize,
+    pub bits: usize,
+    pub order: BitOrder,
+    pub before: BigUint,
+    pub after: BigUint,
+}
+
+impl JournalEntry {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.bits
+    }
+}
+
+/// `START BITS ORDER BEFORE AFTER`, with the values in hex.
+impl std::fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let order = match self.order {
+            BitOrder::Msb => "msb",
+            BitOrder::Lsb => "lsb",
+        };
+        write!(
+            f,
+            "{} {} {} {:#x} {:#x}",
+            self.start, self.bits, order, self.before, self.after
+        )
+    }
+}
+
+impl FromStr for JournalEntry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid journal entry '{}'", s);
+        let hex = |v: &str| {
+            v.strip_prefix("0x")
+                .and_then(|v| BigUint::parse_bytes(v.as_bytes(), 16))
+                .ok_or_else(invalid)
+        };
+        let [start, bits, order, before, after] = s.split_whitespace().collect::<Vec<_>>()[..]
+        else {
+            return Err(invalid());
+        };
+        Ok(JournalEntry {
+            start: start.parse().map_err(|_| invalid())?,
+            bits: bits.parse().map_err(|_| invalid())?,
+            order: match order {
+                "msb" => BitOrder::Msb,
+                "lsb" => BitOrder::Lsb,
+                _ => return Err(invalid()),
+            },
+            before: hex(before)?,
+            after: hex(after)?,
+        })
+    }
+}
+
+/// Where the undo journal for `file` is kept: beside it, with
+/// `.itty-bitty-undo` appended to its name.
+pub fn journal_path(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".itty-bitty-undo");
+    PathBuf::from(name)
+}
+
+/// Add `entry` to the end of the journal at `path`, creating it if needed.
+pub fn append_journal(path: &Path, entry: &JournalEntry) -> io::Result<()> {
+    let mut journal = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(journal, "{}", entry)?;
+    journal.sync_all()
+}
+
+/// The entries in the journal at `path`, oldest first; none if it doesn't
+/// exist.
+pub fn read_journal(path: &Path) -> io::Result<Vec<JournalEntry>> {
+    let journal = match File::open(path) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    BufReader::new(journal)
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+        .map(|line| {
+            line?
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect()
+}
+
+/// Replace the journal at `path` with `entries`, removing it if there are
+/// none left.
+pub fn write_journal(path: &Path, entries: &[JournalEntry]) -> io::Result<()> {
+    if entries.is_empty() {
+        return std::fs::remove_file(path);
+    }
+    let mut journal = File::create(path)?;
+    for entry in entries {
+        writeln!(journal, "{}", entry)?;
+    }
+    journal.sync_all()
+}
@@ -0,0 +1,121 @@
+//! Tests for reading fields from members of zip and tar archives
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use std::io::Write;
+use zip::write::SimpleFileOptions;
+
+const PI: &[u8] = b"BZh91AY&SY";
+
+/// A zip holding `lib/pi.bin` (stored) and `README.md` (deflated).
+fn create_test_zip() -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("lib/pi.bin", stored).unwrap();
+    zip.write_all(PI).unwrap();
+    let deflated = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("README.md", deflated).unwrap();
+    zip.write_all(readme_content().repeat(4).as_bytes()).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_split_member_path() {
+    assert_eq!(split_member_path("bundle.zip::lib/foo.so"), ("bundle.zip", vec!["lib/foo.so"]));
+    assert_eq!(
+        split_member_path("outer.tar::inner.zip::a"),
+        ("outer.tar", vec!["inner.zip", "a"])
+    );
+    assert_eq!(split_member_path("plain.bin"), ("plain.bin", vec![]));
+    // A file whose name really contains "::" is taken as is
+    let path = create_file("odd::name.bin", PI);
+    let path = path.to_str().unwrap();
+    assert_eq!(split_member_path(path), (path, vec![]));
+}
+
+#[test]
+fn test_find_zip_members() {
+    let zip = create_test_zip();
+    assert_eq!(ArchiveFormat::detect(&zip), Some(ArchiveFormat::Zip));
+
+    let member = find_member(&zip, "lib/pi.bin").unwrap();
+    assert!(!member.compressed);
+    assert_eq!(member.size, PI.len() as u64);
+    let start = member.data_offset as usize;
+    assert_eq!(&zip[start..start + PI.len()], PI);
+    let input = Input::read_all(&zip[..]).unwrap().into_member(&member).unwrap();
+    assert_eq!(&input[..], PI);
+
+    let member = find_member(&zip, "README.md").unwrap();
+    assert!(member.compressed);
+    assert!(member.stored_size < member.size);
+    let input = Input::read_all(&zip[..]).unwrap().into_member(&member).unwrap();
+    assert_eq!(&input[..], readme_content().repeat(4).as_bytes());
+
+    assert_eq!(
+        find_member(&zip, "missing").unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    );
+    assert!(find_member(PI, "lib/pi.bin").is_err());
+}
+
+#[test]
+fn test_find_tar_member() {
+    let mut tar = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(PI.len() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, "lib/pi.bin", PI).unwrap();
+    let tar = tar.into_inner().unwrap();
+    assert_eq!(ArchiveFormat::detect(&tar), Some(ArchiveFormat::Tar));
+
+    let member = find_member(&tar, "lib/pi.bin").unwrap();
+    assert_eq!(member.data_offset, 512);
+    // A leading ./ matches either way
+    assert_eq!(find_member(&tar, "./lib/pi.bin").unwrap(), Member {
+        name: "./lib/pi.bin".into(),
+        ..member
+    });
+}
+
+#[test]
+fn test_cli_zip_member() {
+    let path = create_file("bundle.zip", &create_test_zip());
+    let path = path.to_str().unwrap();
+
+    let pi = format!("{}::lib/pi.bin", path);
+    assert_eq!(run(&[&pi, "4:0", "48"]), "0x314159265359");
+    assert_eq!(run(&[&pi, "--", "-6:0", "48"]), "0x314159265359");
+    assert_eq!(run(&["--search", "0x314159265359", &pi]), "0x4:0");
+    // Relative to the zip, the member's data follows its local header
+    let member = find_member(&create_test_zip(), "lib/pi.bin").unwrap();
+    assert_eq!(
+        run(&["--container-offsets", "--search", "0x314159265359", &pi]),
+        format!("{:#x}:0", member.data_offset + 4)
+    );
+
+    let readme = format!("{}::README.md", path);
+    assert_eq!(run(&["-f", "ascii", &readme, "2:0", "10:0"]), "itty-bitty");
+    assert_eq!(run(&[&format!("{}::missing", path), "0", "8"]), "");
+}
+
+#[test]
+fn test_cli_tar_member() {
+    let Some(path) = create_tar() else { return };
+    let member = format!("{}::README.md", path.to_str().unwrap());
+    assert_eq!(run(&["-f", "ascii", &member, "2:0", "10:0"]), "itty-bitty");
+    // The first member's data follows its 512-byte header
+    assert_eq!(
+        run(&["-f", "ascii", "--container-offsets", &member, "514:0", "10:0"]),
+        "itty-bitty"
+    );
+}
+
+#[test]
+fn test_cli_compressed_tar_member() {
+    let Some(path) = create_tar_xz() else { return };
+    let member = format!("{}::README.md", path.to_str().unwrap());
+    assert_eq!(run(&["-f", "ascii", &member, "2:0", "10:0"]), "itty-bitty");
+}
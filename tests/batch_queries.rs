@@ -0,0 +1,89 @@
+//! Tests for --batch query files and streams
+
+mod common;
+
+use common::*;
+use std::process::Command;
+
+#[test]
+fn test_batch_file() {
+    let path = create_file("batch.bin", &pe_like());
+    let queries = test_dir().join("batch.queries");
+    std::fs::write(
+        &queries,
+        "# DOS header\n\
+         0 2B ascii dos magic\n\
+         \n\
+         0x3c:0 32 lsb e_lfanew\n\
+         @[0x3c:32le] 2B ascii\n\
+         @[0x3c:32le]+4B 16 lsb machine\n\
+         0x84:0..0x86:0 decimal\n",
+    )
+    .unwrap();
+
+    // Queries without a label are labelled with their offset
+    assert_eq!(
+        run(&["--batch", queries.to_str().unwrap(), path.to_str().unwrap()]),
+        "dos magic\tMZ\n\
+         e_lfanew\t0x80\n\
+         0x80:0\tPE\n\
+         machine\t0x8664\n\
+         0x84:0\t25734"
+    );
+}
+
+#[test]
+fn test_batch_stdin() {
+    let path = create_file("batch_stdin.bin", &pe_like());
+    let file = path.to_str().unwrap();
+
+    // Spaces inside brackets, parentheses and quotes don't split a query
+    assert_eq!(
+        run_with_stdin(
+            &["--batch", "-", file],
+            b"@[ 0x3c : 32le ] 16 hex\n(2 * 8) 8 decimal third byte\nafter:\"PE\" 16 lsb\n"
+        ),
+        "0x80:0\t0x5045\nthird byte\t0\n0x82:0\t0x0"
+    );
+
+    // Errors name the line they came from
+    assert!(
+        run_with_stdin(&["--batch", "-", file], b"0 8\n\n0x200:0 8\n")
+            .starts_with("Error: \"<stdin>:3: Requested range exceeds file size")
+    );
+    assert!(run_with_stdin(&["--batch", "-", file], b"zz 8\n").starts_with("Error: \"<stdin>:1: "));
+    assert!(run_with_stdin(&["--batch", "-", "-"], b"0 8\n").contains("stdin"));
+}
+
+#[test]
+fn test_batch_records() {
+    let path = create_file("batch_records.bin", &pe_like());
+    let file = path.to_str().unwrap();
+
+    let out = run_with_stdin(&["-o", "ndjson", "--batch", "-", file], b"0 16 magic\n0x3c:0 32\n");
+    let records: Vec<serde_json::Value> =
+        out.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records[0]["label"], "magic");
+    assert_eq!(records[0]["hex"], "0x4d5a");
+    assert_eq!(records[1]["label"], "0x3c:0");
+
+    let out = run_with_stdin(&["-o", "csv", "--batch", "-", file], b"0 16 magic\n");
+    assert!(out.lines().next().unwrap().ends_with(",float,label"));
+    assert!(out.lines().nth(1).unwrap().ends_with(",magic"));
+}
+
+#[test]
+fn test_batch_conflicts() {
+    let path = create_file("batch_conflicts.bin", &pe_like());
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["--batch", "-", "--fields", "0:8", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    // Only FILE is positional
+    assert_eq!(
+        run_with_stdin(&["--batch", "-", path.to_str().unwrap(), "0", "8"], b"0 8\n"),
+        "Error: \"Expected only FILE when using --batch\""
+    );
+}
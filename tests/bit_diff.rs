@@ -0,0 +1,137 @@
+//! Tests for --diff and bit-level comparison
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use std::process::Command;
+
+fn base() -> Vec<u8> {
+    (0..=255u8).cycle().take(1024).collect()
+}
+
+fn changed() -> Vec<u8> {
+    let mut data = base();
+    data[0x10] ^= 0x0c;
+    data[0x11] ^= 0x80;
+    data[0x300] ^= 0xff;
+    data
+}
+
+#[test]
+fn test_diff_ranges() {
+    let (a, b) = (base(), changed());
+    let (ra, rb) = (BitReader::new(&a), BitReader::new(&b));
+    let bits = ra.len_bits();
+
+    assert_eq!(
+        diff_ranges(&ra, 0..bits, &rb, 0..bits, BitOrder::Msb).unwrap(),
+        vec![0x80 + 4..0x80 + 6, 0x88..0x89, 0x1800..0x1808]
+    );
+    // LSB numbering counts from the bottom of each byte
+    assert_eq!(
+        diff_ranges(&ra, 0..bits, &rb, 0..bits, BitOrder::Lsb).unwrap(),
+        vec![0x80 + 2..0x80 + 4, 0x8f..0x90, 0x1800..0x1808]
+    );
+    assert!(diff_ranges(&ra, 0..bits, &ra, 0..bits, BitOrder::Msb).unwrap().is_empty());
+
+    // Ranges can start anywhere, and only the shorter length is compared
+    let c = shifted(&a, 3);
+    let rc = BitReader::new(&c);
+    assert!(diff_ranges(&ra, 0..bits, &rc, 3..rc.len_bits(), BitOrder::Msb).unwrap().is_empty());
+}
+
+#[test]
+fn test_best_shift() {
+    let a = base();
+    let c = shifted(&a, 5);
+    let (ra, rc) = (BitReader::new(&a), BitReader::new(&c));
+    let bits = ra.len_bits();
+
+    assert_eq!(best_shift(&ra, 0..bits, &rc, 0, 8, 4096, BitOrder::Msb).unwrap(), (5, 0));
+    assert_eq!(best_shift(&rc, 5..bits + 5, &ra, 0, 8, 4096, BitOrder::Msb).unwrap(), (0, 0));
+    // Out of reach, the best there is still differs
+    assert_ne!(best_shift(&ra, 0..bits, &rc, 0, 2, 4096, BitOrder::Msb).unwrap().1, 0);
+}
+
+#[test]
+fn test_cli_diff() {
+    let a = create_file("diff_a.bin", &base());
+    let mut longer = changed();
+    longer.extend_from_slice(b"xyz");
+    let b = create_file("diff_b.bin", &longer);
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    assert_eq!(
+        run(&["--diff", b, a]),
+        "0x10:4..0x10:6\t2 bits\n\
+         0x11:0..0x11:1\t1 bits\n\
+         0x300:0..0x301:0\t8 bits\n\
+         0x400:0..0x403:0\t24 bits only in ".to_string()
+            + b
+    );
+    // Within a range of both files
+    assert_eq!(run(&["--diff", b, a, "0x11:0..0x12:0"]), "0x11:0..0x11:1\t1 bits");
+
+    // Exit status 1 when anything differs, like cmp
+    let status = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .code()
+    };
+    assert_eq!(status(&["--diff", a, a]), Some(0));
+    assert_eq!(status(&["--diff", b, a]), Some(1));
+    assert_eq!(status(&["--diff", b, a, "0x20:0..0x300:0"]), Some(0));
+}
+
+#[test]
+fn test_cli_diff_same_file() {
+    let path = create_file("diff_same.bin", &base());
+    let file = path.to_str().unwrap();
+
+    // The data repeats every 256 bytes
+    assert_eq!(run(&["--diff", file, "--diff-offset", "0x100:0", file, "0..0x100:0"]), "");
+    assert_eq!(
+        run(&["--diff", file, "--diff-offset", "0x101:0", file, "0..1B"]),
+        "0x0:7..0x1:0\t1 bits\t0x101:7..0x102:0"
+    );
+}
+
+#[test]
+fn test_cli_diff_shift() {
+    let a = base();
+    let path_a = create_file("diff_shift_a.bin", &a);
+    let path_c = create_file("diff_shift_c.bin", &shifted(&a, 3));
+    let (a, c) = (path_a.to_str().unwrap(), path_c.to_str().unwrap());
+
+    // Byte for byte almost everything differs
+    assert!(run(&["--diff", c, a]).lines().count() > 100);
+
+    let out = run(&["--diff-shift", "8", "--diff", c, a]);
+    assert_eq!(
+        out,
+        format!(
+            "Best alignment: {} shifted +3 bits, to 0x0:3 (0 bits differ in the first 8192 bits)\n\
+             0x400:3..0x401:0\t5 bits only in {}",
+            c, c
+        )
+    );
+}
+
+#[test]
+fn test_cli_diff_dump() {
+    let a = create_file("diff_dump_a.bin", &base());
+    let b = create_file("diff_dump_b.bin", &changed());
+
+    let out = run(&["-f", "hex-ascii", "--diff", b.to_str().unwrap(), a.to_str().unwrap()]);
+    let lines: Vec<&str> = out.lines().collect();
+    // Unchanged rows collapse to *, changed ones show both sides with the
+    // changed hex digits highlighted
+    assert_eq!(lines[0], "*");
+    assert!(lines[1].starts_with("- 010: 1\x1b[1;31m0\x1b[0m \x1b[1;31m1\x1b[0m1 12 "));
+    assert!(lines[2].starts_with("+ 010: 1\x1b[1;31mc\x1b[0m \x1b[1;31m9\x1b[0m1 12 "));
+    assert_eq!(lines.iter().filter(|l| l.starts_with("- ")).count(), 2);
+}
@@ -0,0 +1,127 @@
+//! Tests for bit-granular pattern search
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use num_bigint::BigUint;
+
+#[test]
+fn test_pattern_widths() {
+    let hex: Pattern = "0x0f".parse().unwrap();
+    assert_eq!((hex.value, hex.bits), (BigUint::from(15u8), 8));
+    let bin: Pattern = "0b0_101".parse().unwrap();
+    assert_eq!((bin.value, bin.bits), (BigUint::from(5u8), 4));
+    let dec: Pattern = "1000".parse().unwrap();
+    assert_eq!(dec.bits, 10);
+    assert!("0x".parse::<Pattern>().is_err());
+    assert_eq!("0xzz".parse::<Pattern>(), Err(PatternError::Invalid("0xzz".into())));
+    assert_eq!("\"\"".parse::<Pattern>(), Err(PatternError::EmptyText));
+}
+
+#[test]
+fn test_search_every_bit_offset() {
+    // π magic shifted 3 bits into the data, and again byte-aligned
+    let shifted = (0x314159265359u64 << 13).to_be_bytes();
+    let mut data = shifted.to_vec();
+    data.extend_from_slice(&[0x31, 0x41, 0x59, 0x26, 0x53, 0x59]);
+    let reader = BitReader::new(&data);
+    let pi: Pattern = "0x314159265359".parse().unwrap();
+
+    assert_eq!(search(&reader, &pi, BitOrder::Msb, 1), vec![3, 64]);
+    assert_eq!(search(&reader, &pi, BitOrder::Msb, 8), vec![64]);
+}
+
+#[test]
+fn test_search_long_pattern() {
+    let data: Vec<u8> = (0..64u8).collect();
+    let reader = BitReader::new(&data);
+    // 80 bits, longer than the 64-bit window
+    let pattern: Pattern = "0x1011121314151617_1819".parse().unwrap();
+    assert_eq!(search(&reader, &pattern, BitOrder::Msb, 1), vec![0x10 * 8]);
+}
+
+#[test]
+fn test_search_lsb() {
+    // 0b110 LSB-first means stream bits 0, 1, 1
+    let data = [0b0011_0000u8];
+    let reader = BitReader::new(&data);
+    let pattern: Pattern = "0b110".parse().unwrap();
+    assert_eq!(search(&reader, &pattern, BitOrder::Lsb, 1), vec![3]);
+    // And it matches what a read at that offset returns
+    assert_eq!(reader.read_u64(3..6, BitOrder::Lsb).unwrap(), 0b110);
+}
+
+#[test]
+fn test_cli_search() {
+    let path = create_file("search.bin", b"BZh91AY&SY..1AY&SY");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run(&["--search", "0x314159265359", path]), "0x4:0\n0xc:0");
+    assert_eq!(run(&["--search", "0x59", "-a", "16", path]), "0x6:0\n0xe:0");
+    assert_eq!(run(&["--search", "0xffff", path]), "");
+    assert_eq!(run_with_stdin(&["--search", "0xzz", path], b""), "Error: \"Invalid pattern '0xzz'\"");
+}
+
+#[test]
+fn test_pattern_wildcards_and_masks() {
+    let bin: Pattern = "0b1111_1111_1111_xxxx".parse().unwrap();
+    assert_eq!((bin.value, bin.mask, bin.bits), (BigUint::from(0xfff0u16), BigUint::from(0xfff0u16), 16));
+    let hex: Pattern = "0xF?F".parse().unwrap();
+    assert_eq!((hex.value, hex.mask), (BigUint::from(0xf0fu16), BigUint::from(0xf0fu16)));
+    // Value bits outside the mask are dropped
+    let masked: Pattern = "0xFFF5/0xFFF0".parse().unwrap();
+    assert_eq!((masked.value, masked.mask, masked.bits), (BigUint::from(0xfff0u16), BigUint::from(0xfff0u16), 16));
+    assert!("12x".parse::<Pattern>().is_err());
+    assert!("0xff/0x?f".parse::<Pattern>().is_err());
+}
+
+#[test]
+fn test_search_masked() {
+    // MPEG audio frame sync: 11 set bits, then anything
+    let data = [0x00, 0xff, 0xfb, 0x90, 0x00, 0xff, 0xe3, 0x18, 0xff, 0x00];
+    let reader = BitReader::new(&data);
+    let sync: Pattern = "0b1111_1111_111x_xxxx".parse().unwrap();
+    assert_eq!(search(&reader, &sync, BitOrder::Msb, 8), vec![8, 40]);
+    let sync: Pattern = "0xFFE0/0xFFE0".parse().unwrap();
+    assert_eq!(search(&reader, &sync, BitOrder::Msb, 8), vec![8, 40]);
+
+    // Wildcards in a pattern longer than the 64-bit window
+    let data: Vec<u8> = (0..64u8).collect();
+    let reader = BitReader::new(&data);
+    let pattern: Pattern = "0x1011121314151617_18??".parse().unwrap();
+    assert_eq!(search(&reader, &pattern, BitOrder::Msb, 1), vec![0x10 * 8]);
+}
+
+#[test]
+fn test_search_masked_lsb() {
+    // 0b1x0 LSB-first means stream bits 0, anything, 1
+    let data = [0b0010_1100u8];
+    let reader = BitReader::new(&data);
+    let pattern: Pattern = "0b1x0".parse().unwrap();
+    assert_eq!(search(&reader, &pattern, BitOrder::Lsb, 1), vec![0, 1]);
+}
+
+#[test]
+fn test_search_range() {
+    let data = b"SYxxSYxxSY";
+    let reader = BitReader::new(data);
+    let sy: Pattern = "0x5359".parse().unwrap();
+    assert_eq!(search_range(&reader, &sy, BitOrder::Msb, 1, 8..80), vec![32, 64]);
+    // Matches must lie entirely inside the range
+    assert_eq!(search_range(&reader, &sy, BitOrder::Msb, 1, 0..47), vec![0]);
+    // Alignment is counted from the start of the data, not the range
+    assert_eq!(search_range(&reader, &sy, BitOrder::Msb, 64, 8..80), vec![64]);
+}
+
+#[test]
+fn test_cli_search_window() {
+    let path = create_file("search_window.bin", b"SYxxSYxxSY");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run(&["--search", "0x5359", path, "1:0", "9:0"]), "0x4:0\n0x8:0");
+    assert_eq!(run(&["--search", "0x5359", "1:0", "9:0", path]), "0x4:0\n0x8:0");
+    // The last four bytes
+    assert_eq!(run(&["--search", "0x53??", path, "--", "-4:0", "4:0"]), "0x8:0");
+    assert_eq!(run(&["--search", "0x5359/0xff00", path, "0", "6:0"]), "0x0:0\n0x4:0");
+}
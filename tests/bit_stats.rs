@@ -0,0 +1,81 @@
+//! Tests for --analyze and statistics over bit ranges
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+
+#[test]
+fn test_analyze() {
+    // Every byte value once: 8 bits of entropy per byte, 1 per bit
+    let data: Vec<u8> = (0..=255).collect();
+    let reader = BitReader::new(&data);
+    let stats = analyze(&reader, 0..2048, BitOrder::Msb).unwrap();
+    assert_eq!((stats.bits, stats.ones, stats.bytes()), (2048, 1024, 256));
+    assert_eq!(stats.byte_entropy(), 8.0);
+    assert_eq!(stats.bit_entropy(), 1.0);
+    assert!((0..8).all(|p| stats.bias(p) == 0.5));
+
+    // Constant data has none, and one run
+    let zeros = [0u8; 16];
+    let stats = analyze(&BitReader::new(&zeros), 0..128, BitOrder::Msb).unwrap();
+    assert_eq!((stats.byte_entropy(), stats.bit_entropy()), (0.0, 0.0));
+    assert_eq!((stats.runs, stats.longest_zeros, stats.longest_ones), (1, 128, 0));
+
+    assert_eq!(analyze(&reader, 8..8, BitOrder::Msb), Err(ReadError::EmptyRead));
+    assert!(matches!(
+        analyze(&reader, 2040..2056, BitOrder::Msb),
+        Err(ReadError::PastEnd { .. })
+    ));
+}
+
+#[test]
+fn test_analyze_unaligned() {
+    // 0x55 repeated, shifted 3 bits: realigned it's 0x55 again
+    let data = [0xea, 0xaa, 0xaa, 0xaa, 0xa0];
+    let stats = analyze(&BitReader::new(&data), 3..35, BitOrder::Msb).unwrap();
+    assert_eq!(stats.histogram[0x55], 4);
+    assert_eq!(stats.byte_entropy(), 0.0);
+    assert_eq!(stats.runs, 32);
+    // The first bit of each realigned byte is clear, the second set
+    assert_eq!((stats.bias(0), stats.bias(1)), (0.0, 1.0));
+
+    // With LSB numbering the first bit is the bottom one
+    let stats = analyze(&BitReader::new(&[0x0f, 0xf0]), 4..12, BitOrder::Lsb).unwrap();
+    assert_eq!(stats.histogram[0x00], 1);
+    assert_eq!(stats.longest_zeros, 8);
+
+    // A partial final byte counts towards the bits but not the histogram
+    let stats = analyze(&BitReader::new(&[0xff, 0xf0]), 0..12, BitOrder::Msb).unwrap();
+    assert_eq!((stats.bytes(), stats.ones, stats.position_bits[3], stats.position_bits[4]), (1, 12, 2, 1));
+}
+
+#[test]
+fn test_cli_analyze() {
+    let mut data = vec![0u8; 64];
+    data.extend((0..=255u8).cycle().take(192));
+    let path = create_file("analyze.bin", &data);
+    let file = path.to_str().unwrap();
+
+    let out = run(&["--analyze", file, "0", "32B"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "range: 0x0:0..0x20:0 (256 bits, 32 whole bytes)");
+    assert_eq!(lines[1], "entropy: 0.0000 bits/byte, 0.0000 bits/bit");
+    assert_eq!(lines[2], "popcount: 0 ones, 256 zeros (0.00% ones)");
+    assert_eq!(lines[4], "runs: 1 (1.0 expected if random), mean 256.00 bits, longest 256 zeros, 0 ones");
+    assert_eq!(lines[5..], ["histogram:", "0x00\t32\t100.00%"]);
+
+    // Windows show where the zeros end; one that would run past the end of
+    // the range is left out
+    assert_eq!(
+        run(&["--analyze", "--window", "64B", "--step", "48B", file, "0..0xef:0"]),
+        "offset\tentropy/byte\tentropy/bit\tones\truns/expected\tlongest run\n\
+         0x0:0\t0.0000\t0.0000\t0.00%\t1.000\t512\n\
+         0x30:0\t4.9143\t0.8113\t25.00%\t0.870\t143\n\
+         0x60:0\t6.0000\t0.9887\t43.75%\t1.138\t7\n\
+         0x90:0\t6.0000\t1.0000\t50.00%\t1.058\t8"
+    );
+
+    assert!(run_with_stdin(&["--analyze", "--window", "512B", file], b"").contains("longer than the range"));
+    assert!(run_with_stdin(&["--analyze", "--window", "0", file], b"").contains("at least 1 bit"));
+}
@@ -0,0 +1,216 @@
+//! Tests for patching fields with BitWriter, --write and --undo
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use num_bigint::{BigInt, BigUint};
+use std::process::Command;
+
+#[test]
+fn test_write_round_trip() {
+    let value = BigUint::from(0x2d5u32);
+    for order in [BitOrder::Msb, BitOrder::Lsb] {
+        for range in [0..10, 3..13, 13..23, 7..17] {
+            let mut data = [0xa5u8; 4];
+            let original = data;
+            BitWriter::new(&mut data).write(range.clone(), &value, order).unwrap();
+            let reader = BitReader::new(&data);
+            assert_eq!(reader.read(range.clone(), order).unwrap(), value, "{:?} {:?}", order, range);
+
+            // Every bit outside the field is as it was
+            let before = BitReader::new(&original);
+            for bit in (0..32).filter(|b| !range.contains(b)) {
+                assert_eq!(
+                    reader.read(bit..bit + 1, order).unwrap(),
+                    before.read(bit..bit + 1, order).unwrap()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_write_byte_orders() {
+    let value = BigUint::from(0x0102030405060708u64);
+    for order in [BitOrder::Msb, BitOrder::Lsb] {
+        for byte_order in [
+            ByteOrder::Big,
+            ByteOrder::Little,
+            ByteOrder::Pdp,
+            ByteOrder::WordSwap16,
+            ByteOrder::WordSwap32,
+        ] {
+            let mut data = [0u8; 9];
+            BitWriter::new(&mut data)
+                .write_with_byte_order(4..68, &value, order, byte_order)
+                .unwrap();
+            assert_eq!(
+                BitReader::new(&data)
+                    .read_with_byte_order(4..68, order, byte_order)
+                    .unwrap(),
+                value
+            );
+        }
+    }
+
+    let mut data = [0u8; 4];
+    BitWriter::new(&mut data)
+        .write_with_byte_order(0..32, &BigUint::from(0xdeadbeefu32), BitOrder::Msb, ByteOrder::Little)
+        .unwrap();
+    assert_eq!(data, [0xef, 0xbe, 0xad, 0xde]);
+}
+
+#[test]
+fn test_write_errors() {
+    let mut data = [0u8; 2];
+    let mut writer = BitWriter::new(&mut data);
+    assert_eq!(
+        writer.write(0..8, &BigUint::from(0x100u32), BitOrder::Msb),
+        Err(WriteError::ValueTooWide { value_bits: 9, bits: 8 })
+    );
+    assert_eq!(parse_value(" zz "), Err(WriteError::InvalidValue("zz".into())));
+    assert!(matches!(
+        writer.write(12..20, &BigUint::from(1u8), BitOrder::Msb),
+        Err(WriteError::Range(ReadError::PastEnd { .. }))
+    ));
+    assert!(matches!(
+        writer.write_with_byte_order(0..12, &BigUint::from(1u8), BitOrder::Msb, ByteOrder::Pdp),
+        Err(WriteError::Range(ReadError::NotWholeWords { .. }))
+    ));
+    assert_eq!(data, [0, 0]);
+}
+
+#[test]
+fn test_from_signed() {
+    for encoding in [
+        SignedEncoding::TwosComplement,
+        SignedEncoding::OnesComplement,
+        SignedEncoding::SignMagnitude,
+        SignedEncoding::OffsetBinary,
+    ] {
+        for n in -7..=7 {
+            let n = BigInt::from(n);
+            let raw = from_signed(&n, 4, encoding, None).unwrap();
+            assert_eq!(to_signed(&raw, 4, encoding, None), n, "{:?}", encoding);
+        }
+    }
+    let n = |v: i32| BigInt::from(v);
+    assert_eq!(from_signed(&n(-8), 4, SignedEncoding::TwosComplement, None), Some(BigUint::from(8u8)));
+    assert_eq!(from_signed(&n(8), 4, SignedEncoding::TwosComplement, None), None);
+    assert_eq!(from_signed(&n(-8), 4, SignedEncoding::SignMagnitude, None), None);
+    assert_eq!(
+        from_signed(&n(-3), 8, SignedEncoding::OffsetBinary, Some(&n(127))),
+        Some(BigUint::from(124u8))
+    );
+}
+
+#[test]
+fn test_journal_entries() {
+    let entry = JournalEntry {
+        start: 17,
+        bits: 3,
+        order: BitOrder::Lsb,
+        before: BigUint::from(0u8),
+        after: BigUint::from(5u8),
+    };
+    assert_eq!(entry.to_string(), "17 3 lsb 0x0 0x5");
+    assert_eq!(entry.to_string().parse::<JournalEntry>().unwrap(), entry);
+    assert!("17 3 0x0 0x5".parse::<JournalEntry>().is_err());
+}
+
+#[test]
+fn test_cli_write_and_undo() {
+    let path = create_file("patch.bin", &[0xff, 0xff, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78]);
+    let file = path.to_str().unwrap();
+    let journal = journal_path(&path);
+
+    // A dry run shows the change and leaves the file and journal alone
+    assert_eq!(run(&["-n", "--write", "0x5a", file, "4", "8"]), "0x0:4 [8 bits]: 0xff -> 0x5a");
+    assert_eq!(std::fs::read(&path).unwrap()[..2], [0xff, 0xff]);
+    assert!(!journal.exists());
+
+    assert_eq!(run(&["--write", "0x5a", file, "4", "8"]), "0x0:4 [8 bits]: 0xff -> 0x5a");
+    assert_eq!(run(&["-e", "lsb", "--write", "0b101", file, "2:1", "3"]), "0x2:1 [3 bits]: 0x0 -> 0x5");
+    assert_eq!(
+        run(&["-B", "little", "--write", "0xdeadbeef", file, "4:0", "32"]),
+        "0x4:0 [32 bits]: 0x78563412 -> 0xdeadbeef"
+    );
+    assert_eq!(
+        run(&["-f", "decimal", "-s", "twos", "--write", "-2", file, "3:4", "4"]),
+        "0x3:4 [4 bits]: 0 -> -2"
+    );
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        [0xf5, 0xaf, 0x0a, 0x0e, 0xef, 0xbe, 0xad, 0xde]
+    );
+    assert_eq!(read_journal(&journal).unwrap().len(), 4);
+
+    // Undo reverts the patches newest first, shown as --write shows them,
+    // then removes the journal
+    assert_eq!(
+        run(&["-f", "decimal", "-s", "twos", "--undo", file]),
+        "0x3:4 [4 bits]: -2 -> 0"
+    );
+    assert_eq!(
+        run(&["-B", "little", "--undo", file]),
+        "0x4:0 [32 bits]: 0xdeadbeef -> 0x78563412"
+    );
+    for _ in 0..2 {
+        let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+            .args(["--undo", file])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    }
+    assert_eq!(std::fs::read(&path).unwrap(), [0xff, 0xff, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78]);
+    assert!(!journal.exists());
+    assert!(run_with_stdin(&["--undo", file], b"").starts_with("Error: \"Nothing to undo"));
+}
+
+#[test]
+fn test_cli_dry_run_read_only() {
+    let path = create_file("patch_read_only.bin", &[0x12, 0x34]);
+    let file = path.to_str().unwrap();
+    let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&path, permissions).unwrap();
+    // Left by an earlier run as root, which can write the file
+    let _ = std::fs::remove_file(journal_path(&path));
+
+    // A preview only reads FILE, so it doesn't need to be writable
+    assert_eq!(run_with_stdin(&["-n", "--write", "0xff", file, "0", "8"], b""), "0x0:0 [8 bits]: 0x12 -> 0xff");
+    assert_eq!(std::fs::read(&path).unwrap(), [0x12, 0x34]);
+    assert!(!journal_path(&path).exists());
+
+    // A patch that fails (except as root, who can write anyway) leaves no
+    // journal entry behind
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["--write", "0xff", file, "0", "8"])
+        .output()
+        .unwrap();
+    assert_eq!(journal_path(&path).exists(), output.status.success());
+}
+
+#[test]
+fn test_cli_write_refusals() {
+    let path = create_file("patch_refusals.bin", &[0u8; 4]);
+    let file = path.to_str().unwrap();
+
+    let error = |args: &[&str]| run_with_stdin(args, b"");
+    assert_eq!(
+        error(&["--write", "0x100", file, "0", "8"]),
+        "Error: \"Value needs 9 bits but the field is only 8 bits\""
+    );
+    assert_eq!(error(&["--write", "-129", file, "0", "8"]), "Error: \"-129 doesn't fit in 8 bits\"");
+    assert_eq!(error(&["--write", "zz", file, "0", "8"]), "Error: \"Invalid value 'zz'\"");
+    assert!(error(&["--write", "1", "-", "0", "8"]).contains("Can only patch a plain FILE"));
+    assert!(error(&["-n", file, "0", "8"]).contains("--dry-run needs --write or --undo"));
+    assert_eq!(std::fs::read(&path).unwrap(), [0u8; 4]);
+
+    // A patch that's since been overwritten isn't undone
+    run(&["--write", "0xab", file, "0", "8"]);
+    std::fs::write(&path, [0xcd, 0, 0, 0]).unwrap();
+    assert!(error(&["--undo", file]).contains("not undoing"));
+    assert_eq!(std::fs::read(&path).unwrap(), [0xcd, 0, 0, 0]);
+}
@@ -0,0 +1,85 @@
+//! Tests for byte order applied independently of bit order
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use num_bigint::BigUint;
+
+const DATA: &[u8] = &[0x0a, 0x0b, 0x0c, 0x0d, 0x01, 0x02, 0x03, 0x04, 0xa5];
+
+fn read(range: std::ops::Range<usize>, order: BitOrder, byte_order: ByteOrder) -> BigUint {
+    BitReader::new(DATA)
+        .read_with_byte_order(range, order, byte_order)
+        .unwrap()
+}
+
+#[test]
+fn test_byte_orders() {
+    let hex = |v: BigUint| format!("{:x}", v);
+    assert_eq!(hex(read(0..64, BitOrder::Msb, ByteOrder::Big)), "a0b0c0d01020304");
+    assert_eq!(hex(read(0..64, BitOrder::Msb, ByteOrder::Little)), "40302010d0c0b0a");
+    assert_eq!(hex(read(0..32, BitOrder::Msb, ByteOrder::Pdp)), "b0a0d0c");
+    assert_eq!(hex(read(0..64, BitOrder::Msb, ByteOrder::WordSwap16)), "30401020c0d0a0b");
+    assert_eq!(hex(read(0..64, BitOrder::Msb, ByteOrder::WordSwap32)), "10203040a0b0c0d");
+    assert_eq!(hex(read(0..64, BitOrder::Msb, ByteOrder::WordSwap64)), "a0b0c0d01020304");
+}
+
+#[test]
+fn test_defaults_match_plain_read() {
+    // MSB + big and LSB + little are what plain reads already do, at any
+    // bit offset
+    let reader = BitReader::new(DATA);
+    for start in 0..8 {
+        let range = start..start + 64;
+        assert_eq!(
+            read(range.clone(), BitOrder::Msb, ByteOrder::Big),
+            reader.read(range.clone(), BitOrder::Msb).unwrap()
+        );
+        assert_eq!(
+            read(range.clone(), BitOrder::Lsb, ByteOrder::Little),
+            reader.read(range, BitOrder::Lsb).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_unaligned_little_endian() {
+    // 0x1234 little-endian (34 12) starting 4 bits in
+    let reader = BitReader::new(&[0x03, 0x41, 0x20]);
+    let value = reader
+        .read_with_byte_order(4..20, BitOrder::Msb, ByteOrder::Little)
+        .unwrap();
+    assert_eq!(value, BigUint::from(0x1234u32));
+}
+
+#[test]
+fn test_not_whole_words() {
+    let reader = BitReader::new(DATA);
+    assert_eq!(
+        reader.read_with_byte_order(0..12, BitOrder::Msb, ByteOrder::Little),
+        Err(ReadError::NotWholeWords {
+            bits: 12,
+            word_bits: 8
+        })
+    );
+    assert_eq!(
+        reader.read_with_byte_order(0..48, BitOrder::Msb, ByteOrder::WordSwap32),
+        Err(ReadError::NotWholeWords {
+            bits: 48,
+            word_bits: 32
+        })
+    );
+}
+
+#[test]
+fn test_cli_byte_order() {
+    let path = create_file("byte_order.bin", DATA);
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run(&["-B", "little", path, "0", "32"]), "0xd0c0b0a");
+    assert_eq!(run(&["-B", "le", "-e", "lsb", path, "0", "32"]), "0xd0c0b0a");
+    // Whole bytes read the same under either bit numbering
+    assert_eq!(run(&["-B", "big", "-e", "lsb", path, "0", "32"]), "0xa0b0c0d");
+    assert_eq!(run(&["-B", "pdp", "-f", "decimal", path, "0", "32"]), "185208076");
+}
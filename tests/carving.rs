@@ -0,0 +1,97 @@
+//! Tests for --carve and realigning bit ranges to bytes
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use std::process::Command;
+
+fn carve(data: &[u8], range: std::ops::Range<usize>, order: BitOrder, padding: Padding) -> Vec<u8> {
+    BitReader::new(data)
+        .aligned_chunks(range, order, padding, 2)
+        .unwrap()
+        .flatten()
+        .collect()
+}
+
+#[test]
+fn test_aligned_chunks() {
+    let data = [0x12, 0x34, 0x56, 0x78, 0x9a];
+    assert_eq!(carve(&data, 4..24, BitOrder::Msb, Padding::Zero), [0x23, 0x45, 0x60]);
+    assert_eq!(carve(&data, 4..24, BitOrder::Msb, Padding::One), [0x23, 0x45, 0x6f]);
+    assert_eq!(carve(&data, 4..24, BitOrder::Lsb, Padding::Zero), [0x41, 0x63, 0x05]);
+    assert_eq!(carve(&data, 4..24, BitOrder::Lsb, Padding::One), [0x41, 0x63, 0xf5]);
+    assert_eq!(carve(&data, 8..40, BitOrder::Msb, Padding::One), [0x34, 0x56, 0x78, 0x9a]);
+
+    // Whatever the chunk size, the bytes are the same
+    let reader = BitReader::new(&data);
+    for chunk_bytes in [1, 3, 64] {
+        let chunks = reader.aligned_chunks(3..38, BitOrder::Msb, Padding::Zero, chunk_bytes).unwrap();
+        assert_eq!((chunks.len_bytes(), chunks.padding_bits()), (5, 5));
+        assert_eq!(
+            chunks.flatten().collect::<Vec<_>>(),
+            carve(&data, 3..38, BitOrder::Msb, Padding::Zero)
+        );
+    }
+
+    assert!(matches!(
+        reader.aligned_chunks(8..48, BitOrder::Msb, Padding::Zero, 16),
+        Err(ReadError::PastEnd { .. })
+    ));
+}
+
+#[test]
+fn test_cli_carve() {
+    let payload: Vec<u8> = (0..=255).collect();
+    let path = create_file("carve.bin", &shifted(&payload, 3));
+    let file = path.to_str().unwrap();
+    let out = test_dir().join("carved.bin");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["--carve", out.to_str().unwrap(), file, "3", "256B"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(&out).unwrap(), payload);
+
+    // To stdout, with a partial final byte padded as asked
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["--carve", "-", "--pad", "one", file, "3..15"])
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, [0x00, 0x0f]);
+
+    assert!(run_with_stdin(&["--carve", "-", file, "3", "300B"], b"").contains("exceeds file size"));
+    assert!(run_with_stdin(&["--pad", "one", file, "0", "8"], b"").contains("--carve"));
+
+    // -n only previews --write and --undo; it doesn't quietly carve anyway
+    let dry = test_dir().join("carved_dry.bin");
+    let _ = std::fs::remove_file(&dry);
+    let error = run_with_stdin(&["-n", "--carve", dry.to_str().unwrap(), file, "3", "8"], b"");
+    assert!(error.contains("--dry-run needs --write or --undo"));
+    assert!(!dry.exists());
+    assert!(run_with_stdin(&["-n", "--analyze", file], b"").contains("--dry-run needs"));
+}
+
+#[test]
+fn test_cli_carve_embedded_stream() {
+    let Some(bz2) = create_bzip2() else {
+        return;
+    };
+    let stream = std::fs::read(&bz2).unwrap();
+    let path = create_file("embedded.bin", &shifted(&stream, 5));
+    let out = test_dir().join("recovered.bz2");
+
+    // Carve from the stream's unaligned magic, and it decompresses again
+    let length = format!("{}B", stream.len());
+    let status = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["--carve", out.to_str().unwrap(), path.to_str().unwrap(), "before:0x425a68", &length])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(std::fs::read(&out).unwrap(), stream);
+    assert_eq!(
+        run(&["-z", "-f", "ascii", out.to_str().unwrap(), "0", "12B"]),
+        "# itty-bitty"
+    );
+}
@@ -1,6 +1,10 @@
+#![allow(dead_code)] // each test binary uses a different subset
+
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicU32, Ordering};
 
 static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
@@ -16,12 +20,74 @@ pub fn readme_content() -> &'static str {
     "# itty-bitty test file\n\nThis is test content for archive format testing.\n"
 }
 
-pub fn create_readme(dir: &PathBuf) -> PathBuf {
+pub fn create_readme(dir: &Path) -> PathBuf {
     let path = dir.join("README.md");
     fs::write(&path, readme_content()).expect("Failed to write README.md");
     path
 }
 
+pub fn create_file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = test_dir().join(name);
+    fs::write(&path, bytes).expect("Failed to write test file");
+    path
+}
+
+/// `payload` preceded by `bits` (1 to 7) set bits, so it starts mid-byte
+pub fn shifted(payload: &[u8], bits: usize) -> Vec<u8> {
+    let mut data = vec![0u8; payload.len() + 1];
+    data[0] = !(0xffu8 >> bits);
+    for (i, &b) in payload.iter().enumerate() {
+        data[i] |= b >> bits;
+        data[i + 1] |= b << (8 - bits);
+    }
+    data
+}
+
+/// A PE-style file: "MZ", a little-endian pointer at 0x3c to "PE\0\0", a
+/// little-endian machine type, then a big-endian 16-bit pointer to "WXYZ"
+pub fn pe_like() -> Vec<u8> {
+    let mut data = vec![0u8; 256];
+    data[..2].copy_from_slice(b"MZ");
+    data[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+    data[0x80..0x84].copy_from_slice(b"PE\0\0");
+    data[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+    data[0x86..0x88].copy_from_slice(&0xc0u16.to_be_bytes());
+    data[0xc0..0xc4].copy_from_slice(b"WXYZ");
+    data
+}
+
+/// Run itty-bitty with raw arguments and return trimmed stdout
+pub fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(args)
+        .output()
+        .expect("Failed to run itty-bitty");
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Run itty-bitty with `input` piped to its stdin and return trimmed stdout,
+/// or stderr if it failed
+pub fn run_with_stdin(args: &[&str], input: &[u8]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run itty-bitty");
+    // The child may exit on an error before reading all of its input
+    if let Err(e) = child.stdin.take().unwrap().write_all(input) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "Failed to write stdin: {}", e);
+    }
+    let output = child.wait_with_output().expect("Failed to run itty-bitty");
+    let out = if output.status.success() {
+        output.stdout
+    } else {
+        output.stderr
+    };
+    String::from_utf8_lossy(&out).trim().to_string()
+}
+
 pub fn has_command(cmd: &str) -> bool {
     Command::new("which")
         .arg(cmd)
@@ -177,3 +243,24 @@ pub fn read_bits_decimal_lsb(file: &str, offset: i64, bits: usize) -> u64 {
         .parse()
         .unwrap_or(0)
 }
+
+/// Decode FILE with a structure definition and map field names to values
+pub fn read_struct(def: &str, file: &str) -> HashMap<String, String> {
+    let def = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(def);
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .arg("--struct")
+        .arg(def)
+        .arg(file)
+        .output()
+        .expect("Failed to run itty-bitty");
+    // name  bytes:bits  [bits]  value...
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?.to_string();
+            let value = parts.skip(2).collect::<Vec<_>>().join(" ");
+            Some((name, value))
+        })
+        .collect()
+}
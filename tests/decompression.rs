@@ -0,0 +1,107 @@
+//! Tests for reading fields from decompressed gzip, bzip2, xz and zstd data
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use std::io::Write;
+
+const PAYLOAD: &[u8] = b"\x89PNG\r\n\x1a\n payload bytes";
+
+fn compress(data: &[u8], format: Compression) -> Vec<u8> {
+    match format {
+        Compression::Gzip => {
+            let mut enc = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            enc.write_all(data).unwrap();
+            enc.finish().unwrap()
+        }
+        Compression::Bzip2 => {
+            let mut enc = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            enc.write_all(data).unwrap();
+            enc.finish().unwrap()
+        }
+        Compression::Xz => {
+            let mut enc = xz2::write::XzEncoder::new(Vec::new(), 6);
+            enc.write_all(data).unwrap();
+            enc.finish().unwrap()
+        }
+        Compression::Zstd => zstd::encode_all(data, 0).unwrap(),
+    }
+}
+
+const FORMATS: [Compression; 4] = [
+    Compression::Gzip,
+    Compression::Bzip2,
+    Compression::Xz,
+    Compression::Zstd,
+];
+
+#[test]
+fn test_detect_and_decompress() {
+    for format in FORMATS {
+        let compressed = compress(PAYLOAD, format);
+        assert_eq!(Compression::detect(&compressed), Some(format));
+        assert_eq!(decompress(&compressed, format).unwrap(), PAYLOAD);
+    }
+    assert_eq!(Compression::detect(b"plain text"), None);
+}
+
+#[test]
+fn test_concatenated_streams() {
+    for format in FORMATS {
+        let mut compressed = compress(b"first ", format);
+        compressed.extend(compress(b"second", format));
+        assert_eq!(decompress(&compressed, format).unwrap(), b"first second");
+    }
+}
+
+#[test]
+fn test_input_decompress() {
+    let mut input = Input::read_all(&compress(PAYLOAD, Compression::Xz)[..]).unwrap();
+    assert_eq!(input.decompress(None).unwrap(), Compression::Xz);
+    assert_eq!(&input[..], PAYLOAD);
+
+    let mut input = Input::read_all(&b"plain text"[..]).unwrap();
+    assert!(input.decompress(None).is_err());
+    let mut input = Input::read_all(&compress(PAYLOAD, Compression::Xz)[..]).unwrap();
+    assert!(input.decompress(Some(Compression::Gzip)).is_err());
+}
+
+#[test]
+fn test_cli_decompress() {
+    for format in FORMATS {
+        let path = create_file(
+            &format!("payload.{}", format.name()),
+            &compress(PAYLOAD, format),
+        );
+        let path = path.to_str().unwrap();
+
+        assert_eq!(run(&["-z", "-f", "ascii", path, "1:0", "3:0"]), "PNG");
+        let forced = format!("--decompress={}", format.name());
+        assert_eq!(run(&[&forced, path, "0", "32"]), "0x89504e47");
+        // Negative offsets count from the end of the payload
+        assert_eq!(run(&["-z", "-f", "ascii", path, "--", "-5:0", "5:0"]), "bytes");
+        // Without -z the container itself is read
+        assert_ne!(run(&[path, "0", "32"]), "0x89504e47");
+    }
+}
+
+#[test]
+fn test_cli_decompress_verbose() {
+    let compressed = compress(PAYLOAD, Compression::Gzip);
+    let path = create_file("verbose.gz", &compressed);
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["-v", "-z", path.to_str().unwrap(), "0", "8"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!(
+            "Decompressed gzip: {} bytes -> {} bytes",
+            compressed.len(),
+            PAYLOAD.len()
+        )),
+        "{}",
+        stderr
+    );
+}
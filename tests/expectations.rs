@@ -0,0 +1,117 @@
+//! Tests for --expect checks and their exit codes
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use num_bigint::BigInt;
+use std::process::Command;
+
+fn expect(s: &str) -> Expectation {
+    s.parse().unwrap()
+}
+
+fn n(value: i64) -> BigInt {
+    BigInt::from(value)
+}
+
+#[test]
+fn test_values() {
+    assert!(expect("0xfeedfacf").matches(&n(0xfeedfacf)));
+    assert!(expect("4_277_009_103").matches(&n(0xfeedfacf)));
+    assert!(expect("0b1010").matches(&n(10)));
+    assert!(expect("-3").matches(&n(-3)));
+    assert!(expect("\"MZ\"").matches(&n(0x4d5a)));
+    // Text can hold `..` without being a range
+    assert!(expect("\"a..b\"").matches(&n(0x612e2e62)));
+    assert!(!expect("0x4d5b").matches(&n(0x4d5a)));
+
+    // Masks, inline or added later, compare only their set bits
+    assert!(expect("0x0c/0x0f").matches(&n(0x1c)));
+    assert!(!expect("0x0c/0x0f").matches(&n(0x1d)));
+    let masked = expect("0x1c").with_mask("0x0f").unwrap();
+    assert!(masked.matches(&n(0xfc)));
+    assert_eq!(masked.to_string(), "0xc under mask 0xf");
+}
+
+#[test]
+fn test_ranges() {
+    assert!(expect("1..=7").matches(&n(7)));
+    assert!(!expect("1..7").matches(&n(7)));
+    assert!(!expect("1..=7").matches(&n(0)));
+    assert!(expect("..0x100").matches(&n(-5)));
+    assert!(expect("8..").matches(&n(1 << 40)));
+    assert!(!expect("8..").matches(&n(7)));
+    assert_eq!(expect("0x10..=0x1f").to_string(), "0x10..=0x1f");
+    assert!(expect("\"a\"..\"a..b\"").matches(&n(0x612e2e)));
+
+    for s in ["", "..", "1..=", "0xzz", "1/-1", "\"\"", "1..x"] {
+        assert!(s.parse::<Expectation>().is_err(), "{:?} should not parse", s);
+    }
+    assert_eq!("0xzz".parse::<Expectation>(), Err(ExpectError::Invalid("0xzz".into())));
+    assert_eq!(expect("1").with_mask("-1"), Err(ExpectError::NegativeMask("-1".into())));
+}
+
+/// Run itty-bitty and return its exit code, stdout and stderr
+fn run_checked(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(args)
+        .output()
+        .expect("Failed to run itty-bitty");
+    (
+        output.status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    )
+}
+
+#[test]
+fn test_cli_expect() {
+    let path = create_file("expect.bin", b"MZ\x90\x00\x03\x00");
+    let file = path.to_str().unwrap();
+
+    assert_eq!(
+        run_checked(&["--expect", "\"MZ\"", file, "0", "16"]),
+        (0, "0x4d5a".into(), String::new())
+    );
+
+    let (code, stdout, stderr) = run_checked(&["--expect", "0x5a4d", file, "0", "16"]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "0x4d5a");
+    assert_eq!(stderr, "0x0:0 [16 bits]: expected 0x5a4d, got 0x4d5a");
+
+    // Every field is checked, and each mismatch reported
+    let (code, _, stderr) = run_checked(&[
+        "--fields", "0:16,8,8",
+        "--expect", "\"MZ\"",
+        "--expect", "0x80..=0x8f",
+        "--expect", "1..=7",
+        file,
+    ]);
+    assert_eq!(code, 1);
+    assert_eq!(
+        stderr,
+        "0x2:0 [8 bits]: expected 0x80..=0x8f, got 0x90\n0x3:0 [8 bits]: expected 1..=7, got 0"
+    );
+
+    let (code, _, stderr) = run_checked(&[
+        "--expect", "0x3", "--expect-mask", "0x0f", "-B", "little", file, "4:0", "16",
+    ]);
+    assert_eq!((code, stderr.as_str()), (0, ""));
+    let (code, _, stderr) = run_checked(&["-s", "twos", "--expect", "-1..0", file, "2:0", "8"]);
+    assert_eq!(code, 1);
+    assert_eq!(stderr, "0x2:0 [8 bits]: expected -1..0, got -112");
+
+    // Structured output still lists every field before the check fails
+    let (code, stdout, _) = run_checked(&["-o", "ndjson", "--expect", "0", file, "0", "8"]);
+    assert_eq!(code, 1);
+    assert!(stdout.contains("\"hex\":\"0x4d\""));
+
+    let (code, _, stderr) = run_checked(&["--expect", "1", "--expect", "2", file, "0", "8"]);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("2 --expect values for 1 fields"));
+
+    let (code, _, stderr) = run_checked(&["--expect", "zz", file, "0", "8"]);
+    assert_eq!(code, 1);
+    assert_eq!(stderr, "Error: \"Invalid --expect: Invalid expected value 'zz'\"");
+}
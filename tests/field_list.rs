@@ -0,0 +1,85 @@
+//! Tests for reading several fields in one invocation
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+
+#[test]
+fn test_parse_field_list() {
+    let fields = parse_field_list("0:16,0x10:3:8,4,2.4").unwrap();
+    assert_eq!(fields.len(), 4);
+    assert_eq!(fields[0], FieldSpec::new(Offset::from_bits(0), Length::from_bits(16)));
+    assert_eq!(fields[1].offset, Some(Offset::from_bits(0x10 * 8 + 3)));
+    assert_eq!(fields[1].length.to_bits(), 8);
+    assert_eq!(fields[2], FieldSpec::chained(Length::from_bits(4)));
+    assert_eq!(fields[3], FieldSpec::chained(Length::from_bits(20)));
+
+    assert!(parse_field_list("0:16,,8").is_err());
+    assert!(parse_field_list("0:9:8").is_err());
+}
+
+#[test]
+fn test_parse_field_list_commas() {
+    // Commas in quoted text and pointers don't split the list
+    let fields = parse_field_list("after:\"a,b\":8,@[1,000:8]:8").unwrap();
+    assert_eq!(fields.len(), 2);
+
+    // But can't group digits, which would be ambiguous
+    assert!(parse_field_list("0:8,1,000:8").is_err());
+    assert!(parse_field_list("0:8,1_000:8").is_ok());
+    assert_eq!(parse_field_list("8, 100").unwrap().len(), 2);
+    assert_eq!(parse_field_list("8,1000").unwrap().len(), 2);
+}
+
+#[test]
+fn test_resolve_chained_fields() {
+    let data = [0u8; 8];
+    let reader = BitReader::new(&data);
+    let fields = parse_field_list("12,4,-16:8,8").unwrap();
+    assert_eq!(
+        reader.resolve_fields(&fields).unwrap(),
+        vec![0..12, 12..16, 48..56, 56..64]
+    );
+
+    let too_far = parse_field_list("60,8").unwrap();
+    assert!(matches!(
+        reader.resolve_fields(&too_far),
+        Err(ReadError::PastEnd { end_bit: 68, .. })
+    ));
+}
+
+#[test]
+fn test_cli_field_list() {
+    let path = create_file("fields.bin", b"BZh91AY&SY");
+    let path = path.to_str().unwrap();
+
+    let out = run(&["-F", "0:16,16:8,24:8,48", path]);
+    assert_eq!(
+        out,
+        "0x0:0\t0x425a\n0x2:0\t0x68\n0x3:0\t0x39\n0x4:0\t0x314159265359"
+    );
+}
+
+#[test]
+fn test_cli_repeated_reads() {
+    let path = create_file("fields.bin", b"BZh91AY&SY");
+    let path = path.to_str().unwrap();
+
+    let out = run(&["-f", "ascii", "-r", "0", "24", "-r", "-16", "16", path]);
+    assert_eq!(out, "0x0:0\tBZh\n0x8:0\tSY");
+
+    // --fields entries come before --read ones
+    let out = run(&["-f", "decimal", "-r", "0", "8", "-F", "8:8", path]);
+    assert_eq!(out, "0x1:0\t90\n0x0:0\t66");
+}
+
+#[test]
+fn test_cli_single_field_unlabelled() {
+    let path = create_file("fields.bin", b"BZh91AY&SY");
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run(&["-F", "0:16", path]), "0x425a");
+    // Positional OFFSET LENGTH can't be combined with a field list
+    assert_eq!(run(&["-F", "0:16", path, "0", "8"]), "");
+}
@@ -0,0 +1,104 @@
+//! Tests for floating-point output formats
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use num_bigint::BigUint;
+
+fn decode(hex: &str, format: FloatFormat) -> FloatParts {
+    decode_float(&BigUint::parse_bytes(hex.as_bytes(), 16).unwrap(), format)
+}
+
+// ============================================================================
+// Decoding
+// ============================================================================
+
+#[test]
+fn test_binary32_pi() {
+    let parts = decode("40490fdb", FloatFormat::Binary32);
+    assert!(!parts.sign);
+    assert_eq!(parts.exponent, 0x80);
+    assert_eq!(parts.unbiased_exponent(), Some(1));
+    assert_eq!(parts.mantissa, BigUint::from(0x490fdbu32));
+    assert_eq!(parts.class, FloatClass::Normal);
+    assert_eq!(parts.to_string(), "3.1415927");
+}
+
+#[test]
+fn test_classes() {
+    assert_eq!(decode("8000", FloatFormat::Binary16).class, FloatClass::Zero);
+    assert_eq!(decode("8000", FloatFormat::Binary16).to_string(), "-0");
+    assert_eq!(decode("0001", FloatFormat::Binary16).class, FloatClass::Subnormal);
+    assert_eq!(decode("ff80", FloatFormat::Bfloat16).to_string(), "-inf");
+    assert_eq!(
+        decode("7ff0000000000005", FloatFormat::Binary64).class,
+        FloatClass::Nan {
+            quiet: false,
+            payload: BigUint::from(5u8)
+        }
+    );
+    assert_eq!(
+        decode("7fffc000000000000001", FloatFormat::X87Extended).class,
+        FloatClass::Nan {
+            quiet: true,
+            payload: BigUint::from(1u8)
+        }
+    );
+    // x87 unnormal: integer bit clear with a non-zero exponent
+    assert_eq!(
+        decode("40004000000000000000", FloatFormat::X87Extended).class,
+        FloatClass::Invalid
+    );
+}
+
+#[test]
+fn test_values() {
+    assert_eq!(decode("3c00", FloatFormat::Binary16).to_string(), "1");
+    assert_eq!(decode("7bff", FloatFormat::Binary16).to_string(), "65504");
+    assert_eq!(decode("4049", FloatFormat::Bfloat16).to_string(), "3.141");
+    assert_eq!(decode("bfb999999999999a", FloatFormat::Binary64).to_string(), "-0.1");
+    assert_eq!(decode("0000000000000001", FloatFormat::Binary64).to_string(), "5e-324");
+    assert_eq!(
+        decode("4000921fb54442d18469898cc51701b8", FloatFormat::Binary128).to_string(),
+        "3.1415926535897932384626433832795028"
+    );
+    assert_eq!(
+        decode("4000c90fdaa22168c235", FloatFormat::X87Extended).to_string(),
+        "3.14159265358979323851"
+    );
+    assert_eq!(
+        decode("3fff8000000000000000", FloatFormat::X87Extended).to_string(),
+        "1"
+    );
+}
+
+// ============================================================================
+// CLI
+// ============================================================================
+
+#[test]
+fn test_cli_unaligned_float() {
+    // binary32 pi shifted right by 3 bits
+    let pi = 0x40490fdbu64 << 5;
+    let path = create_file("float.bin", &pi.to_be_bytes()[3..]);
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run(&["-f", "f32", path, "0:3", "32"]), "3.1415927");
+    assert_eq!(run(&["-f", "binary32", path, "3", "32"]), "3.1415927");
+    // Wrong width is an error, so nothing on stdout
+    assert_eq!(run(&["-f", "f32", path, "3", "16"]), "");
+}
+
+#[test]
+fn test_cli_breakdown() {
+    let path = create_file("float.bin", &[0xff, 0xc0, 0x00, 0x01]);
+    let path = path.to_str().unwrap();
+
+    let out = run(&["-f", "f32", "--breakdown", path, "0", "32"]);
+    assert!(out.contains("sign: 1 (-)"), "{}", out);
+    assert!(out.contains("exponent: 0xff (255 biased)"), "{}", out);
+    assert!(out.contains("mantissa: 0x400001"), "{}", out);
+    assert!(out.contains("class: quiet NaN (payload 0x1)"), "{}", out);
+    assert!(out.ends_with("NaN"), "{}", out);
+}
@@ -0,0 +1,102 @@
+//! Tests for `@[OFFSET:BITS]` offsets that follow pointers in the data
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use std::process::Command;
+
+fn offset(s: &str) -> Offset {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_pointer() {
+    let data = pe_like();
+    let reader = BitReader::new(&data);
+
+    let pe = offset("@[0x3c:32le]");
+    assert!(pe.needs_data());
+    assert_eq!(reader.resolve_offset(&pe).unwrap(), 0x80 * 8);
+    assert_eq!(
+        reader.pointers(Some(&pe), None).unwrap(),
+        vec![Pointer {
+            at: 0x3c * 8,
+            bits: 32,
+            byte_order: ByteOrder::Little,
+            value: 0x80,
+        }]
+    );
+
+    // Big-endian unless told otherwise, and plain numbers count bytes
+    // inside the brackets but not outside them
+    assert_eq!(reader.resolve_offset(&offset("@[0x3c:32]")).unwrap(), 0x8000_0000 * 8);
+    assert_eq!(reader.resolve_offset(&offset("@[0x3c:32le] + 4")).unwrap(), 0x80 * 8 + 4);
+    assert_eq!(reader.resolve_offset(&offset("@[0x1e*2:32le]")).unwrap(), 0x80 * 8);
+    assert_eq!(reader.resolve_offset(&offset("@[480b:32le]")).unwrap(), 0x80 * 8);
+    assert_eq!(reader.resolve_offset(&offset("@[end-0xc4:32le]")).unwrap(), 0x80 * 8);
+    assert_eq!(reader.resolve_offset(&offset("@[-0xc4:32le]")).unwrap(), 0x80 * 8);
+}
+
+#[test]
+fn test_nested_pointers() {
+    let data = pe_like();
+    let reader = BitReader::new(&data);
+
+    let trailer = offset("@[@[0x3c:32le] + 6:16be]");
+    let range = reader.resolve(&trailer, &"4B".parse().unwrap()).unwrap();
+    assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 0x5758595a);
+
+    // Innermost first, in the order they were read
+    let chain = reader.pointers(Some(&trailer), None).unwrap();
+    assert_eq!(
+        chain.iter().map(|p| (p.at / 8, p.value)).collect::<Vec<_>>(),
+        vec![(0x3c, 0x80), (0x86, 0xc0)]
+    );
+
+    // Lengths can follow pointers too
+    let length: Length = "@[0x86:16] - 0x80 B".parse().unwrap();
+    assert_eq!(length.resolve_bits(&reader, 0).unwrap(), 0x40 * 8);
+}
+
+#[test]
+fn test_invalid_pointers() {
+    for s in ["@0x3c", "@[0x3c]", "@[0x3c:32le", "@[0x3c:0]", "@[0x3c:65]", "@[0x3c:32xx]"] {
+        assert!(s.parse::<Offset>().is_err(), "{:?} should not parse", s);
+    }
+
+    let data = pe_like();
+    let reader = BitReader::new(&data);
+    assert!(matches!(
+        reader.resolve_offset(&offset("@[0xfe:32]")),
+        Err(ReadError::PastEnd { .. })
+    ));
+}
+
+#[test]
+fn test_cli_pointers() {
+    let path = create_file("pe_like.bin", &pe_like());
+    let file = path.to_str().unwrap();
+
+    assert_eq!(run(&[file, "@[0x3c:32le]", "32"]), "0x50450000");
+    assert_eq!(run(&[file, "@[@[0x3c:32le]+6:16]", "4B"]), "0x5758595a");
+    assert_eq!(
+        run(&["--fields", "@[0x3c:32le]:16,16", file]),
+        "0x80:0\t0x5045\n0x82:0\t0x0"
+    );
+
+    // -v shows where each pointer was read and where it led
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["-v", file, "@[@[0x3c:32le]+6:16]", "4B"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let pointers: Vec<&str> = stderr.lines().filter(|l| l.starts_with("Pointer")).collect();
+    assert_eq!(
+        pointers,
+        [
+            "Pointer at 0x3c:0 (32 bits, little) -> byte 0x80",
+            "Pointer at 0x86:0 (16 bits, big) -> byte 0xc0"
+        ]
+    );
+}
@@ -0,0 +1,146 @@
+//! Tests for the library API, without going through the binary
+
+use itty_bitty::*;
+
+const PI: &[u8] = &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+
+// ============================================================================
+// Offset / Length parsing
+// ============================================================================
+
+#[test]
+fn test_offset_syntax() {
+    let cases = [
+        ("1234", 1234),
+        ("0x10", 16),
+        ("$10", 16),
+        ("10h", 16),
+        ("1_000", 1000),
+        ("2:3", 19),
+        ("0x1A.3", 0x1A * 8 + 3),
+        ("-32", -32),
+        ("-0x10:4", -(0x10 * 8 + 4)),
+    ];
+    for (s, bits) in cases {
+        let offset: Offset = s.parse().unwrap();
+        assert_eq!(offset.to_bits(), bits, "offset {}", s);
+    }
+    assert_eq!("1:8".parse::<Offset>(), Err(OffsetError::InvalidBitOffset));
+    assert!("zz".parse::<Offset>().is_err());
+}
+
+#[test]
+fn test_length_syntax() {
+    assert_eq!("48".parse::<Length>().unwrap().to_bits(), 48);
+    assert_eq!("6:0".parse::<Length>().unwrap().to_bits(), 48);
+    assert_eq!("0x2:1".parse::<Length>().unwrap().to_bits(), 17);
+}
+
+// ============================================================================
+// BitReader
+// ============================================================================
+
+#[test]
+fn test_resolve_negative_offset() {
+    let reader = BitReader::new(PI);
+    let range = reader
+        .resolve(&"-16".parse().unwrap(), &"16".parse().unwrap())
+        .unwrap();
+    assert_eq!(range, 32..48);
+    assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 0x5359);
+}
+
+#[test]
+fn test_resolve_errors() {
+    let reader = BitReader::new(PI);
+    assert_eq!(
+        reader.resolve(&Offset::from_bits(-49), &Length::from_bits(1)),
+        Err(ReadError::NegativeOffsetTooLarge {
+            from_end: 49,
+            total_bits: 48
+        })
+    );
+    assert!(matches!(
+        reader.resolve(&Offset::from_bits(40), &Length::from_bits(9)),
+        Err(ReadError::PastEnd { end_bit: 49, .. })
+    ));
+    assert_eq!(
+        reader.resolve(&Offset::from_bits(0), &Length::from_bits(0)),
+        Err(ReadError::EmptyRead)
+    );
+}
+
+#[test]
+fn test_read_msb_and_lsb() {
+    let reader = BitReader::new(PI);
+    assert_eq!(reader.read_u64(0..48, BitOrder::Msb).unwrap(), 0x314159265359);
+    // LSB: the first byte is the low byte, bits counted from the bottom
+    assert_eq!(reader.read_u64(0..16, BitOrder::Lsb).unwrap(), 0x4131);
+    assert_eq!(reader.read_u64(0..4, BitOrder::Lsb).unwrap(), 0x1);
+    assert_eq!(reader.read_u64(4..12, BitOrder::Msb).unwrap(), 0x14);
+}
+
+#[test]
+fn test_read_native_widths() {
+    let data = [0xffu8; 20];
+    let reader = BitReader::new(&data);
+    assert_eq!(reader.read_u128(0..128, BitOrder::Msb).unwrap(), u128::MAX);
+    assert_eq!(
+        reader.read_u64(0..65, BitOrder::Msb),
+        Err(ReadError::TooWide { bits: 65, max: 64 })
+    );
+    let wide = reader.read(0..160, BitOrder::Lsb).unwrap();
+    assert_eq!(wide.bits(), 160);
+}
+
+#[test]
+fn test_read_wide_unaligned() {
+    // Compare word-based extraction with a bit-at-a-time reference, at
+    // widths either side of the native fast path and at every start bit
+    let data: Vec<u8> = (0..64u32).map(|i| (i * 0x9d + 0x35) as u8).collect();
+    let reader = BitReader::new(&data);
+    let bit = |i: usize, order: BitOrder| match order {
+        BitOrder::Msb => data[i / 8] >> (7 - i % 8) & 1,
+        BitOrder::Lsb => data[i / 8] >> (i % 8) & 1,
+    };
+    for order in [BitOrder::Msb, BitOrder::Lsb] {
+        for start in 0..8 {
+            for width in [1, 7, 31, 33, 64, 100, 127, 128, 129, 200, 333] {
+                let range = start..start + width;
+                let mut expected = num_bigint::BigUint::ZERO;
+                for i in range.clone() {
+                    let pos = match order {
+                        BitOrder::Msb => range.end - 1 - i,
+                        BitOrder::Lsb => i - start,
+                    };
+                    if bit(i, order) == 1 {
+                        expected.set_bit(pos as u64, true);
+                    }
+                }
+                assert_eq!(
+                    reader.read(range.clone(), order).unwrap(),
+                    expected,
+                    "{:?} {:?}",
+                    order,
+                    range
+                );
+                if width <= 128 {
+                    let native = reader.read_u128(range.clone(), order).unwrap();
+                    assert_eq!(num_bigint::BigUint::from(native), expected);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_enum_names() {
+    // The command line's names and aliases, without needing clap
+    assert_eq!("LSB".parse::<BitOrder>(), Ok(BitOrder::Lsb));
+    assert_eq!("le".parse::<ByteOrder>(), Ok(ByteOrder::Little));
+    assert_eq!("word-swap32".parse::<ByteOrder>(), Ok(ByteOrder::WordSwap32));
+    assert_eq!("twos".parse::<SignedEncoding>(), Ok(SignedEncoding::TwosComplement));
+    assert_eq!("zst".parse::<Compression>(), Ok(Compression::Zstd));
+    assert_eq!("ones".parse::<Padding>(), Ok(Padding::One));
+    assert_eq!("middle-out".parse::<ByteOrder>(), Err("unknown byte order 'middle-out'".into()));
+}
@@ -0,0 +1,94 @@
+//! Tests for reading one field across many files and globs
+
+mod common;
+
+use common::*;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A directory of its own, so globs see nothing else, of small files with a
+/// version byte at offset 2
+fn versions(name: &str) -> PathBuf {
+    let dir = test_dir().join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, version) in [("a.bin", 1u8), ("b.bin", 2), ("c.bin", 1), ("d.dat", 3)] {
+        std::fs::write(dir.join(name), [0xca, 0xfe, version]).unwrap();
+    }
+    std::fs::write(dir.join("short.bin"), [0xca]).unwrap();
+    dir
+}
+
+#[test]
+fn test_cli_many_files() {
+    let dir = versions("versions_many");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let (a, b, c) = (path("a.bin"), path("b.bin"), path("c.bin"));
+
+    assert_eq!(
+        run(&["2B", "8", &a, &b, &c]),
+        format!("{}\t0x1\n{}\t0x2\n{}\t0x1", a, b, c)
+    );
+    // Globs are expanded in name order
+    assert_eq!(
+        run(&["2:0..3:0", &path("[a-c].bin")]),
+        format!("{}\t0x1\n{}\t0x2\n{}\t0x1", a, b, c)
+    );
+    // One file, as before, prints just the value
+    assert_eq!(run(&["2B", "8", &a]), "0x1");
+
+    let ndjson = run(&["-o", "ndjson", "-f", "decimal", "2B", "8", &a, &b]);
+    let records: Vec<serde_json::Value> =
+        ndjson.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records[1]["file"], b.as_str());
+    assert_eq!(records[1]["decimal"], "2");
+}
+
+#[test]
+fn test_cli_many_files_group() {
+    let dir = versions("versions_group");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    assert_eq!(
+        run(&["-G", "-f", "decimal", "2B", "8", &path("*.bin"), &path("d.dat")]),
+        format!(
+            "1\t2 files\n\t{}\n\t{}\n2\t1 file\n\t{}\n3\t1 file\n\t{}",
+            path("a.bin"),
+            path("c.bin"),
+            path("b.bin"),
+            path("d.dat")
+        )
+    );
+    assert!(run_with_stdin(&["-G", "2B", "8", &path("a.bin")], b"").contains("--group needs"));
+}
+
+#[test]
+fn test_cli_many_files_errors() {
+    let dir = versions("versions_errors");
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    // A file that can't be read is reported, the rest still are, and the
+    // exit status is 1
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["2B", "8", &path("a.bin"), &path("short.bin"), &path("b.bin")])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\t0x1\n{}\t0x2\n", path("a.bin"), path("b.bin"))
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with(&path("short.bin")));
+
+    // --expect checks every file
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["--expect", "1", "2B", "8", &path("a.bin"), &path("b.bin")])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).trim(),
+        format!("{}: expected 1, got 2", path("b.bin"))
+    );
+
+    assert!(run_with_stdin(&["2B", "8", &path("*.nope")], b"").contains("No files match"));
+}
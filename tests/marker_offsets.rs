@@ -0,0 +1,113 @@
+//! Tests for offsets anchored to `after:` and `before:` markers
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+
+/// A minimal PNG: signature, IHDR, IDAT, IEND, then some trailing bytes
+fn png() -> Vec<u8> {
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]); // CRC, unchecked here
+        chunk
+    }
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend(chunk(b"IHDR", &[0, 0, 0, 2, 0, 0, 0, 3, 8, 2, 0, 0, 0]));
+    png.extend(chunk(b"IDAT", b"xyz"));
+    png.extend(chunk(b"IEND", b""));
+    png.extend_from_slice(b"trailing");
+    png
+}
+
+fn resolve(reader: &BitReader, s: &str) -> Result<usize, ReadError> {
+    reader.resolve_offset(&s.parse().unwrap())
+}
+
+#[test]
+fn test_markers() {
+    let data = png();
+    let reader = BitReader::new(&data);
+    let iend = 8 + 25 + 15 + 4;
+
+    assert_eq!(resolve(&reader, "before:\"IHDR\"").unwrap(), 12 * 8);
+    assert_eq!(resolve(&reader, "after:\"IHDR\"").unwrap(), 16 * 8);
+    assert_eq!(resolve(&reader, "before:-1:\"IEND\"").unwrap(), iend * 8);
+    assert_eq!(resolve(&reader, "after:-1:\"IEND\" + 4B").unwrap(), (iend + 8) * 8);
+    assert_eq!(resolve(&reader, "before:\"IDAT\" - 4B").unwrap(), 33 * 8);
+
+    // Occurrences count from 1, or back from the last
+    assert_eq!(resolve(&reader, "before:2:\"I\"").unwrap(), 8 * 37);
+    assert_eq!(resolve(&reader, "before:-2:\"I\"").unwrap(), 8 * 37);
+
+    // Hex and binary patterns match at any bit offset, with masks and
+    // wildcards as for --search
+    assert_eq!(resolve(&reader, "before:0x4948/0xFFF0").unwrap(), 12 * 8);
+    assert_eq!(resolve(&reader, "after:0x1a0x").unwrap(), 8 * 8);
+    assert_eq!(resolve(&reader, "before:0b10001001010").unwrap(), 0);
+}
+
+#[test]
+fn test_marker_errors() {
+    let data = png();
+    let reader = BitReader::new(&data);
+
+    assert_eq!(
+        resolve(&reader, "before:4:\"I\""),
+        Err(ReadError::MarkerNotFound {
+            marker: "before:4:\"I\"".into(),
+            matches: 3
+        })
+    );
+    assert!(matches!(
+        resolve(&reader, "after:\"JUNK\""),
+        Err(ReadError::MarkerNotFound { matches: 0, .. })
+    ));
+    for s in ["after:", "after:\"\"", "after:0:\"I\"", "after:\"IEND", "after:0xZZ"] {
+        assert!(s.parse::<Offset>().is_err(), "{:?} should not parse", s);
+    }
+}
+
+#[test]
+fn test_text_pattern() {
+    let pattern: Pattern = "\"IEND\"".parse().unwrap();
+    assert_eq!(pattern, Pattern::from_bytes(b"IEND"));
+    assert_eq!(pattern.bits, 32);
+    assert_eq!(pattern.value, 0x49454e44u32.into());
+
+    // Separators are only dropped outside quotes
+    let offset: Offset = "before:\"a,b\" + 1_000".parse().unwrap();
+    let data = b"xxa,b";
+    let reader = BitReader::new(data);
+    assert_eq!(reader.resolve_offset(&offset).unwrap(), 16 + 1000);
+}
+
+#[test]
+fn test_markers_msb_first() {
+    // 0000 1111 1111 0000 MSB-first, but 1111 0000 0000 1111 LSB-first
+    let data = [0x0f, 0xf0, b'B', b'Z'];
+    let reader = BitReader::new(&data);
+    assert_eq!(resolve(&reader, "after:0b1111+0").unwrap(), 8);
+
+    // -e lsb doesn't change where a marker is, and text is found at the
+    // same byte either way
+    let path = create_file("markers_lsb.bin", &data);
+    let file = path.to_str().unwrap();
+    assert_eq!(run(&["-e", "lsb", file, "after:0b1111+0", "4"]), "0x0");
+    assert_eq!(run(&["-e", "lsb", file, "after:\"B\"", "8"]), "0x5a");
+}
+
+#[test]
+fn test_cli_markers() {
+    let path = create_file("markers.png", &png());
+    let file = path.to_str().unwrap();
+
+    assert_eq!(run(&["-f", "ascii", file, "after:-1:\"IEND\"+32", "*"]), "trailing");
+    assert_eq!(
+        run(&["--fields", "after:\"IHDR\":32,32", file]),
+        "0x10:0\t0x2\n0x14:0\t0x3"
+    );
+    assert_eq!(run(&["--search", "\"IEND\"", file]), "0x34:0");
+}
@@ -0,0 +1,98 @@
+//! Tests for arithmetic, units and `end`/`size` in offsets and lengths
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+
+fn offset(s: &str) -> Offset {
+    s.parse().unwrap()
+}
+
+fn length(s: &str) -> Length {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_constant_expressions() {
+    assert_eq!(offset("(0x40+12)*8b").to_bits(), 608);
+    assert_eq!(offset("2B+4b").to_bits(), 20);
+    assert_eq!(offset("0x1B B").to_bits(), 27 * 8);
+    assert_eq!(offset("3 * (4 + 1)").to_bits(), 15);
+    assert_eq!(offset("1KiB - 1B").to_bits(), 1023 * 8);
+    assert_eq!(offset("2 sectors").to_bits(), 2 * 512 * 8);
+    assert_eq!(offset("MiB/8").to_bits(), 1024 * 1024);
+    assert_eq!(length("0x10:4 + 4").to_bits(), 0x10 * 8 + 8);
+
+    // Plain offsets keep their meaning, negative ones included
+    assert_eq!(offset("0x1A:3"), Offset::from_bits(0x1A * 8 + 3));
+    assert_eq!(offset("-0x100:3"), Offset::from_bits(-(0x100 * 8 + 3)));
+    assert_eq!(offset("1,024"), Offset::from_bits(1024));
+    assert!(!offset("-32").needs_data());
+
+    // A negative result counts from the end, like a negative literal
+    assert_eq!(offset("8 - 4B").to_bits(), -24);
+
+    // Any whitespace separates, however many bytes it takes
+    assert_eq!(offset("2B\u{3000}+\u{a0}4b").to_bits(), 20);
+    assert_eq!(offset("4\u{3000}").to_bits(), 4);
+}
+
+#[test]
+fn test_invalid_expressions() {
+    for s in ["", "1 +", "(1", "1)", "2 * * 3", "4 / 0", "1 foo", "1:8"] {
+        assert!(s.parse::<Offset>().is_err(), "{:?} should not parse", s);
+    }
+    assert!("2 - 3".parse::<Length>().is_err());
+}
+
+#[test]
+fn test_end_relative() {
+    let data: Vec<u8> = (0..=255).collect();
+    let reader = BitReader::new(&data);
+
+    let end = offset("end-0x20:3");
+    assert!(end.needs_data());
+    assert_eq!(end.resolve_bits(&reader).unwrap(), 2048 - 0x20 * 8 - 3);
+    assert_eq!(reader.resolve_offset(&end).unwrap(), 2048 - 0x20 * 8 - 3);
+
+    let range = reader.resolve(&offset("size/2"), &length("2B")).unwrap();
+    assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 0x8081);
+
+    // Lengths can depend on the size too
+    let range = reader.resolve(&offset("0xF0 B"), &length("end - 0xF0 B")).unwrap();
+    assert_eq!(range, 0xF0 * 8..2048);
+    assert!(matches!(
+        reader.resolve(&offset("0"), &length("size - 300B")),
+        Err(ReadError::Expression(_))
+    ));
+}
+
+#[test]
+fn test_cli_expressions() {
+    let data: Vec<u8> = (0..=255).collect();
+    let path = create_file("expressions.bin", &data);
+    let file = path.to_str().unwrap();
+
+    assert_eq!(run(&[file, "end-4B", "4B"]), "0xfcfdfeff");
+    assert_eq!(run(&[file, "(0x40+12)*8b", "8"]), "0x4c");
+    assert_eq!(run(&["-f", "decimal", file, "end - 0x20:3", "3"]), "7");
+    assert_eq!(run(&["--fields", "2B:1B,end-1B:1B", file]), "0x2:0\t0x2\n0xff:0\t0xff");
+}
+
+#[test]
+fn test_cli_struct_end_relative_base() {
+    let data: Vec<u8> = (0..=255).collect();
+    let path = create_file("expressions_struct.bin", &data);
+    let def = test_dir().join("expressions_struct.toml");
+    std::fs::write(&def, "[[field]]\nname = \"a\"\nbits = 8\n\n[[field]]\nname = \"b\"\nbits = 8\n").unwrap();
+
+    let out = run(&["--struct", def.to_str().unwrap(), path.to_str().unwrap(), "end-2B"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "a  0xfe:0  [8]  0xfe");
+    assert_eq!(lines[1], "b  0xff:0  [8]  0xff");
+
+    // A field's width has to be known without the data
+    std::fs::write(&def, "[[field]]\nname = \"a\"\nbits = \"end\"\n").unwrap();
+    assert_eq!(run(&["--struct", def.to_str().unwrap(), path.to_str().unwrap()]), "");
+}
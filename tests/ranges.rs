@@ -0,0 +1,87 @@
+//! Tests for START..END ranges and `*` lengths
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+
+fn data() -> Vec<u8> {
+    (0..=255).collect()
+}
+
+fn range(reader: &BitReader, s: &str) -> Result<std::ops::Range<usize>, ReadError> {
+    let field = parse_range(s).unwrap();
+    reader.resolve(field.offset.as_ref().unwrap(), &field.length)
+}
+
+#[test]
+fn test_ranges() {
+    let data = data();
+    let reader = BitReader::new(&data);
+
+    assert_eq!(range(&reader, "0x100..0x200").unwrap(), 0x100..0x200);
+    assert_eq!(range(&reader, "0x10:3..=0x12:5").unwrap(), 0x83..0x96);
+    assert_eq!(range(&reader, "0xfc:0..end").unwrap(), 0xfc * 8..2048);
+    assert_eq!(range(&reader, "0xfc:0..").unwrap(), 0xfc * 8..2048);
+    assert_eq!(range(&reader, "-4B..-1B").unwrap(), 2016..2040);
+    assert_eq!(range(&reader, "2B .. 4B").unwrap(), 16..32);
+
+    // `*` is the rest of the data
+    let rest: Length = "*".parse().unwrap();
+    assert!(rest.needs_data());
+    assert_eq!(rest.resolve_bits(&reader, 2000).unwrap(), 48);
+    let range = reader.resolve(&"-4B".parse().unwrap(), &rest).unwrap();
+    assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 0xfcfdfeff);
+}
+
+#[test]
+fn test_range_errors() {
+    let data = data();
+    let reader = BitReader::new(&data);
+
+    assert_eq!(
+        range(&reader, "0x20..0x10"),
+        Err(ReadError::BackwardRange { start: 0x20, end: 0x10 })
+    );
+    assert_eq!(range(&reader, "0x10..0x10"), Err(ReadError::EmptyRead));
+    assert!(matches!(range(&reader, "0xff:0..=end"), Err(ReadError::PastEnd { .. })));
+    assert!(matches!(
+        range(&reader, "-300B.."),
+        Err(ReadError::NegativeOffsetTooLarge { .. })
+    ));
+    assert!(parse_range("0x10").is_err());
+    assert!(parse_range("0x10..=").is_err());
+    assert!(parse_range("0x10..zz").is_err());
+}
+
+#[test]
+fn test_field_list_ranges() {
+    let fields = parse_field_list("0..2B,..=0x3:7,0xfe:0..").unwrap();
+    assert_eq!(fields[0], FieldSpec::new(Offset::from_bits(0), Length::until(Offset::from_bits(16), false)));
+    assert_eq!(fields[1], FieldSpec::chained(Length::until(Offset::from_bits(31), true)));
+    assert_eq!(fields[2], FieldSpec::new(Offset::from_bits(0xfe * 8), Length::rest()));
+
+    let data = data();
+    let reader = BitReader::new(&data);
+    assert_eq!(
+        reader.resolve_fields(&fields).unwrap(),
+        vec![0..16, 16..32, 0xfe * 8..2048]
+    );
+}
+
+#[test]
+fn test_cli_ranges() {
+    let path = create_file("ranges.bin", &data());
+    let file = path.to_str().unwrap();
+
+    assert_eq!(run(&[file, "0xf0:0..0xf4:0"]), "0xf0f1f2f3");
+    assert_eq!(run(&["0xfc:0..", file]), "0xfcfdfeff");
+    assert_eq!(run(&[file, "0xfc:0", "*"]), "0xfcfdfeff");
+    assert_eq!(run(&[file, "0x10:3..=0x12:5"]), "0x40444");
+    assert_eq!(
+        run(&["--fields", "0..2B,..=0x3:7", file]),
+        "0x0:0\t0x1\n0x2:0\t0x203"
+    );
+    assert_eq!(run(&["--search", "0xfeff", file, "0xf0:0.."]), "0xfe:0");
+    assert_eq!(run(&[file, "0x20..0x10"]), "");
+}
@@ -0,0 +1,91 @@
+//! Tests for signed interpretations of extracted fields
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use num_bigint::{BigInt, BigUint};
+
+// 0xFFF0 0x8001: a 12-bit all-ones field, then a 16-bit 0x8001
+const DATA: &[u8] = &[0xff, 0xf0, 0x80, 0x01];
+
+#[test]
+fn test_encodings_12_bit() {
+    let value = BigUint::from(0xfffu32);
+    let signed = |enc| to_signed(&value, 12, enc, None);
+    assert_eq!(signed(SignedEncoding::TwosComplement), BigInt::from(-1));
+    assert_eq!(signed(SignedEncoding::OnesComplement), BigInt::from(0));
+    assert_eq!(signed(SignedEncoding::SignMagnitude), BigInt::from(-2047));
+    assert_eq!(signed(SignedEncoding::OffsetBinary), BigInt::from(2047));
+    assert_eq!(
+        to_signed(&value, 12, SignedEncoding::OffsetBinary, Some(&BigInt::from(127))),
+        BigInt::from(4095 - 127)
+    );
+}
+
+#[test]
+fn test_positive_values_unchanged() {
+    let value = BigUint::from(0x7ffu32);
+    for enc in [
+        SignedEncoding::TwosComplement,
+        SignedEncoding::OnesComplement,
+        SignedEncoding::SignMagnitude,
+    ] {
+        assert_eq!(to_signed(&value, 12, enc, None), BigInt::from(0x7ff));
+    }
+}
+
+#[test]
+fn test_wider_than_64_bits() {
+    let data = [0xffu8; 12];
+    let reader = BitReader::new(&data);
+    for order in [BitOrder::Msb, BitOrder::Lsb] {
+        let value = reader
+            .read_signed(0..96, order, SignedEncoding::TwosComplement)
+            .unwrap();
+        assert_eq!(value, BigInt::from(-1));
+    }
+    let value = reader
+        .read_signed(0..96, BitOrder::Msb, SignedEncoding::SignMagnitude)
+        .unwrap();
+    assert_eq!(value, -((BigInt::from(1u8) << 95u32) - 1u8));
+}
+
+#[test]
+fn test_cli_signed_msb() {
+    let path = create_file("signed.bin", DATA);
+    let path = path.to_str().unwrap();
+
+    assert_eq!(run(&["-s", "twos", "-f", "decimal", path, "0", "12"]), "-1");
+    assert_eq!(run(&["-s", "twos", "-f", "hex", path, "16", "16"]), "-0x7fff");
+    assert_eq!(
+        run(&["-s", "sign-magnitude", "-f", "decimal", path, "16", "16"]),
+        "-1"
+    );
+    assert_eq!(
+        run(&["-s", "offset-binary", "--bias", "-5", "-f", "decimal", path, "0", "4"]),
+        "20"
+    );
+    // Biases take the same numbers as values do
+    assert_eq!(
+        run(&["-s", "offset-binary", "--bias", "-0b101", "-f", "decimal", path, "0", "4"]),
+        "20"
+    );
+}
+
+#[test]
+fn test_cli_signed_lsb() {
+    let path = create_file("signed.bin", DATA);
+    let path = path.to_str().unwrap();
+
+    // LSB-first: the low 12 bits are 0xff from byte 0 and 0x0 from byte 1
+    assert_eq!(
+        run(&["-e", "lsb", "-s", "twos", "-f", "decimal", path, "0", "12"]),
+        "255"
+    );
+    // LSB-first the first byte is the low byte: 80 01 reads as 0x0180
+    assert_eq!(
+        run(&["-e", "lsb", "-s", "twos", "-f", "decimal", path, "2:0", "16"]),
+        "384"
+    );
+}
@@ -0,0 +1,108 @@
+//! Tests for reading from stdin, pipes and files that can't be mapped
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use std::process::{Command, Stdio};
+
+const BZIP2_HEADER: &[u8] = b"BZh91AY&SY";
+
+#[test]
+fn test_regular_file_is_mapped() {
+    let path = create_file("mapped.bin", BZIP2_HEADER);
+    let input = Input::open(path.to_str().unwrap()).unwrap();
+    assert!(input.is_mapped());
+    assert_eq!(&input[..], BZIP2_HEADER);
+
+    // Nothing to map, but still a valid (empty) input
+    let path = create_file("empty.bin", b"");
+    let input = Input::open(path.to_str().unwrap()).unwrap();
+    assert!(input.is_empty());
+}
+
+#[test]
+fn test_read_all() {
+    let input = Input::read_all(BZIP2_HEADER).unwrap();
+    assert!(!input.is_mapped());
+    let reader = BitReader::new(&input);
+    let range = reader
+        .resolve(&"-6:0".parse().unwrap(), &"48".parse().unwrap())
+        .unwrap();
+    assert_eq!(reader.read_u64(range, BitOrder::Msb).unwrap(), 0x314159265359);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_procfs_file_is_buffered() {
+    // procfs reports a size of zero, so mapping would see no data
+    let input = Input::open("/proc/self/stat").unwrap();
+    assert!(!input.is_mapped());
+    assert!(!input.is_empty());
+}
+
+#[test]
+fn test_cli_stdin() {
+    assert_eq!(run_with_stdin(&["-", "4:0", "48"], BZIP2_HEADER), "0x314159265359");
+    assert_eq!(run_with_stdin(&["4:0", "48", "-"], BZIP2_HEADER), "0x314159265359");
+    // Negative offsets count back from the end of everything read
+    assert_eq!(
+        run_with_stdin(&["-", "--", "-6:0", "6:0"], BZIP2_HEADER),
+        "0x314159265359"
+    );
+    assert_eq!(
+        run_with_stdin(&["--search", "0x314159265359", "-"], BZIP2_HEADER),
+        "0x4:0"
+    );
+    assert!(run_with_stdin(&["-", "0", "16"], b"x").contains("exceeds file size"));
+}
+
+#[test]
+fn test_cli_pipe() {
+    // A real pipeline, as with `curl ... | itty-bitty - 0 48`
+    let mut producer = Command::new("printf")
+        .arg("BZh91AY&SY")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run printf");
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["-f", "ascii", "-", "0", "24"])
+        .stdin(producer.stdout.take().unwrap())
+        .output()
+        .expect("Failed to run itty-bitty");
+    producer.wait().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "BZh");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_endless_pipe() {
+    // Only as much as the fields need is read, so this doesn't run forever
+    let mut producer = Command::new("yes")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run yes");
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["-F", "0:8,8,0..2B", "-"])
+        .stdin(producer.stdout.take().unwrap())
+        .output()
+        .expect("Failed to run itty-bitty");
+    producer.kill().unwrap();
+    producer.wait().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "0x0:0\t0x79\n0x1:0\t0xa\n0x0:0\t0x790a"
+    );
+
+    assert_eq!(run(&["/dev/zero", "1B", "8"]), "0x0");
+}
+
+#[test]
+fn test_cli_missing_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_itty-bitty"))
+        .args(["/nonexistent/itty-bitty.bin", "0", "8"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("/nonexistent/itty-bitty.bin"));
+}
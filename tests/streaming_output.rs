@@ -0,0 +1,110 @@
+//! Tests for streaming byte output (ascii and hex-ascii) over large ranges
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+
+fn test_data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 0x9d + 0x35) as u8).collect()
+}
+
+#[test]
+fn test_byte_chunks_match_integer_read() {
+    // Every chunking must give the same bytes as the integer, zero-padded
+    let data = test_data(200);
+    let reader = BitReader::new(&data);
+    let byte_orders = [
+        ByteOrder::Big,
+        ByteOrder::Little,
+        ByteOrder::Pdp,
+        ByteOrder::WordSwap16,
+        ByteOrder::WordSwap32,
+        ByteOrder::WordSwap64,
+    ];
+    for order in [BitOrder::Msb, BitOrder::Lsb] {
+        for byte_order in byte_orders {
+            for start in [0, 3, 8, 13] {
+                for bits in [5, 64, 77, 640, 1003] {
+                    let range = start..start + bits;
+                    let Ok(value) = reader.read_with_byte_order(range.clone(), order, byte_order)
+                    else {
+                        assert!(reader.byte_chunks(range, order, byte_order, 16).is_err());
+                        continue;
+                    };
+                    let mut expected = value.to_bytes_be();
+                    let num_bytes = bits.div_ceil(8);
+                    expected.splice(0..0, vec![0; num_bytes - expected.len()]);
+
+                    for chunk_bytes in [1, 7, 16, 4096] {
+                        let chunks = reader
+                            .byte_chunks(range.clone(), order, byte_order, chunk_bytes)
+                            .unwrap();
+                        assert_eq!(chunks.len_bytes(), num_bytes);
+                        let bytes: Vec<u8> = chunks.flatten().collect();
+                        assert_eq!(
+                            bytes, expected,
+                            "{:?} {:?} {:?} chunks of {}",
+                            order, byte_order, range, chunk_bytes
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_byte_chunks_bounds() {
+    let data = test_data(16);
+    let reader = BitReader::new(&data);
+    assert!(matches!(
+        reader.byte_chunks(8..136, BitOrder::Msb, ByteOrder::Big, 16),
+        Err(ReadError::PastEnd { .. })
+    ));
+    assert_eq!(
+        reader
+            .byte_chunks(0..24, BitOrder::Msb, ByteOrder::Pdp, 16)
+            .err(),
+        Some(ReadError::NotWholeWords {
+            bits: 24,
+            word_bits: 16
+        })
+    );
+}
+
+#[test]
+fn test_cli_ascii_unaligned() {
+    // "itty-bitty" shifted 3 bits into the file
+    let text = b"itty-bitty";
+    let mut data = vec![0u8; text.len() + 1];
+    for (i, &b) in text.iter().enumerate() {
+        data[i] |= b >> 3;
+        data[i + 1] |= b << 5;
+    }
+    let path = create_file("stream_ascii.bin", &data);
+    let path = path.to_str().unwrap();
+    assert_eq!(run(&["-f", "ascii", path, "0:3", "10:0"]), "itty-bitty");
+}
+
+#[test]
+fn test_cli_hex_ascii_large() {
+    // Spans several stream chunks; every row must be present and in order
+    let data = test_data(200_000);
+    let path = create_file("stream_hex.bin", &data);
+    let path = path.to_str().unwrap();
+    let out = run(&["-f", "hex-ascii", path, "0x10:0", "150000:0"]);
+    let lines: Vec<&str> = out.lines().collect();
+    let width = lines[0].split(" | ").next().unwrap().split_whitespace().count() - 1;
+    assert_eq!(lines.len(), 150_000usize.div_ceil(width));
+    // Offsets are in bytes and start at the field
+    assert!(lines[0].starts_with("00010: "), "{}", lines[0]);
+    let last = lines.last().unwrap();
+    let last_offset = 0x10 + (lines.len() - 1) * width;
+    assert!(last.starts_with(&format!("{:05x}: ", last_offset)), "{}", last);
+    let hex: String = data[last_offset..0x10 + 150_000]
+        .iter()
+        .map(|b| format!("{:02x} ", b))
+        .collect();
+    assert!(last.contains(hex.trim_end()), "{}", last);
+}
@@ -0,0 +1,186 @@
+//! Tests for declarative structure definitions
+
+mod common;
+
+use common::*;
+use itty_bitty::*;
+use itty_bitty::layout::Count;
+use num_bigint::{BigInt, BigUint};
+
+const RECORD: &str = r#"
+name = "record"
+byte_order = "little"
+
+[enums.kind]
+1 = "data"
+2 = "index"
+
+[[field]]
+name = "kind"
+bits = 8
+enum = "kind"
+
+[[field]]
+name = "count"
+bits = 8
+
+[[field]]
+name = "delta"
+bits = 12
+count = "count"
+signed = "twos"
+byte_order = "big"
+
+[[field]]
+name = "checksum"
+bits = 16
+if = "kind == 2"
+display = "decimal"
+
+[[field]]
+name = "tag"
+bits = 16
+offset = -16
+display = "ascii"
+byte_order = "big"
+"#;
+
+fn decode(data: &[u8]) -> Vec<DecodedField> {
+    let layout: Layout = RECORD.parse().unwrap();
+    layout.decode(&BitReader::new(data), 0).unwrap()
+}
+
+#[test]
+fn test_arrays_conditions_enums() {
+    // index record: two 12-bit deltas (-1, 1), checksum 12345 LE, tag "BZ"
+    let fields = decode(&[0x02, 0x02, 0xff, 0xf0, 0x01, 0x39, 0x30, b'B', b'Z']);
+    let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["kind", "count", "delta[0]", "delta[1]", "checksum", "tag"]);
+
+    assert_eq!(fields[0].label.as_deref(), Some("index"));
+    assert_eq!(fields[0].to_string(), "2 (index)");
+    assert_eq!(fields[2].value, FieldValue::Signed(BigInt::from(-1)));
+    assert_eq!(fields[3].value, FieldValue::Signed(BigInt::from(1)));
+    assert_eq!(fields[3].range, 28..40);
+    assert_eq!(fields[4].value, FieldValue::Unsigned(BigUint::from(12345u32)));
+    assert_eq!(fields[4].to_string(), "12345");
+    assert_eq!(fields[5].range, 56..72);
+    assert_eq!(fields[5].to_string(), "\"BZ\"");
+}
+
+#[test]
+fn test_condition_false_skips_field() {
+    // data record with no deltas: checksum is absent
+    let fields = decode(&[0x01, 0x00, b'B', b'Z']);
+    let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["kind", "count", "tag"]);
+}
+
+#[test]
+fn test_definition_errors() {
+    let bad_key = "[[field]]\nname = \"a\"\nbits = 8\nwidth = 3\n";
+    assert!(matches!(bad_key.parse::<Layout>(), Err(LayoutError::Parse(_))));
+
+    let bad_float = "[[field]]\nname = \"a\"\nbits = 16\nfloat = \"f32\"\n";
+    assert!(matches!(bad_float.parse::<Layout>(), Err(LayoutError::Invalid { .. })));
+
+    let forward_ref = "[[field]]\nname = \"a\"\nbits = 8\ncount = \"n\"\n";
+    let layout: Layout = forward_ref.parse().unwrap();
+    assert_eq!(
+        layout.decode(&BitReader::new(&[0]), 0),
+        Err(LayoutError::UnknownField {
+            field: "a".into(),
+            name: "n".into()
+        })
+    );
+}
+
+#[test]
+fn test_count_names_that_look_like_numbers() {
+    let def = "[[field]]\nname = \"dh\"\nbits = 8\n\n[[field]]\nname = \"x\"\nbits = 8\ncount = \"dh\"\n";
+    let layout: Layout = def.parse().unwrap();
+    assert_eq!(layout.fields[1].count, Some(Count::Field("dh".into())));
+    let names: Vec<String> = layout
+        .decode(&BitReader::new(&[2, 0, 0]), 0)
+        .unwrap()
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names, ["dh", "x[0]", "x[1]"]);
+
+    // With no field of that name, it's a number after all
+    let def = "[[field]]\nname = \"x\"\nbits = 8\ncount = \"dh\"\n";
+    let layout: Layout = def.parse().unwrap();
+    assert_eq!(layout.fields[0].count, Some(Count::Fixed(0xd)));
+}
+
+#[test]
+fn test_count_past_end() {
+    // A huge count from the data is rejected up front, not read element by
+    // element
+    let def = "[[field]]\nname = \"n\"\nbits = 32\n\n[[field]]\nname = \"x\"\nbits = 8\ncount = \"n\"\n";
+    let layout: Layout = def.parse().unwrap();
+    let err = layout.decode(&BitReader::new(&[0xff, 0xff, 0xff, 0xff, 0, 0]), 0).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Field 'x': 4294967295 elements of 8 bits don't fit in the 16 bits left"
+    );
+    assert_eq!(layout.decode(&BitReader::new(&[0, 0, 0, 2, 0, 0]), 0).unwrap().len(), 3);
+}
+
+#[test]
+fn test_offsets_with_base() {
+    let def = r#"
+[[field]]
+name = "plain"
+bits = 8
+offset = "1B"
+
+[[field]]
+name = "pointed"
+bits = 8
+offset = "@[0:8]"
+
+[[field]]
+name = "last"
+bits = 8
+offset = "end - 1B"
+"#;
+    let layout: Layout = def.parse().unwrap();
+    let data = [4, 0x10, 0x11, 0x12, 0x13, 0x14];
+    let fields = layout.decode(&BitReader::new(&data), 16).unwrap();
+    let starts: Vec<usize> = fields.iter().map(|f| f.range.start).collect();
+    // A plain offset counts from the base; the others are positions in the
+    // data, the pointer read from byte 0 and pointing to byte 4
+    assert_eq!(starts, [24, 32, 40]);
+}
+
+#[test]
+fn test_cli_mach_header() {
+    // A synthetic little-endian arm64 executable header
+    let mut header = Vec::new();
+    for word in [0xfeedfacfu32, 0x0100000c, 0, 2, 17, 1234, 0x200085, 0] {
+        header.extend_from_slice(&word.to_le_bytes());
+    }
+    let path = create_file("mach_header", &header);
+
+    let fields = read_struct("formats/mach_header_64.toml", path.to_str().unwrap());
+    assert_eq!(fields["magic"], "0xfeedfacf");
+    assert_eq!(fields["cputype"], "0x100000c (ARM64)");
+    assert_eq!(fields["filetype"], "2 (MH_EXECUTE)");
+    assert_eq!(fields["ncmds"], "17");
+    assert_eq!(fields["flags"], "0x200085");
+}
+
+#[test]
+fn test_cli_struct_offset() {
+    let path = create_file("record", &[0xaa, 0x01, 0x00, b'B', b'Z']);
+    let path = path.to_str().unwrap();
+    let def = test_dir().join("record.toml");
+    std::fs::write(&def, RECORD).unwrap();
+
+    let out = run(&["--struct", def.to_str().unwrap(), path, "1:0"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "kind   0x1:0   [8]  1 (data)");
+    assert_eq!(lines[2], "tag    0x3:0  [16]  \"BZ\"");
+}
@@ -0,0 +1,77 @@
+//! Tests for --output json, ndjson and csv
+
+mod common;
+
+use common::*;
+use serde_json::Value;
+
+fn data() -> Vec<u8> {
+    (0..=255).collect()
+}
+
+#[test]
+fn test_json() {
+    let path = create_file("structured.bin", &data());
+    let file = path.to_str().unwrap();
+
+    let out: Value = serde_json::from_str(&run(&["-o", "json", file, "0xfe:0", "16"])).unwrap();
+    let records = out.as_array().unwrap();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record["file"], file);
+    assert_eq!(record["offset"], 2032);
+    assert_eq!(record["position"], "0xfe:0");
+    assert_eq!(record["from_end"], 16);
+    assert_eq!(record["bits"], 16);
+    assert_eq!(record["bit_order"], "msb");
+    assert_eq!(record["byte_order"], "big");
+    assert_eq!(record["hex"], "0xfeff");
+    assert_eq!(record["decimal"], "65279");
+    assert_eq!(record["binary"], "0b1111111011111111");
+    assert_eq!(record["signed"], "-257");
+    assert!(record.get("float").is_none());
+}
+
+#[test]
+fn test_ndjson_fields() {
+    let path = create_file("structured_fields.bin", &data());
+    let file = path.to_str().unwrap();
+
+    let out = run(&["-o", "ndjson", "-e", "lsb", "-s", "sign-magnitude", "--fields", "0x80:0:16,4", file]);
+    let records: Vec<Value> = out.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["byte_order"], "little");
+    assert_eq!(records[0]["hex"], "0x8180");
+    assert_eq!(records[0]["signed"], "-384");
+    assert_eq!(records[1]["position"], "0x82:0");
+    assert_eq!(records[1]["bits"], 4);
+    assert_eq!(records[1]["hex"], "0x2");
+}
+
+#[test]
+fn test_csv() {
+    let path = create_file("structured, csv.bin", &data());
+    let file = path.to_str().unwrap();
+
+    let out = run(&["-o", "csv", "-f", "f16", "--fields", "0x3c:0:16,16", file]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines[0],
+        "file,offset,position,from_end,bits,bit_order,byte_order,hex,decimal,binary,signed,float"
+    );
+    assert_eq!(lines.len(), 3);
+    // The path has a comma, so it's quoted
+    assert_eq!(
+        lines[1],
+        format!("\"{}\",480,0x3c:0,1568,16,msb,big,0x3c3d,15421,0b11110000111101,15421,1.0596", file)
+    );
+    assert!(lines[2].starts_with(&format!("\"{}\",496,0x3e:0,", file)));
+}
+
+#[test]
+fn test_text_unchanged() {
+    let path = create_file("structured_text.bin", &data());
+    let file = path.to_str().unwrap();
+    assert_eq!(run(&["-o", "text", file, "0xfe:0", "16"]), "0xfeff");
+    assert_eq!(run(&["-o", "json", "--search", "0xfeff", file]), "");
+}